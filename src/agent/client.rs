@@ -6,10 +6,13 @@ use crate::error::{Error, Result};
 use crate::public::PublicKey;
 use crate::signature::{Signature, SignatureHash};
 use byteorder::{BigEndian, ByteOrder};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
 use std::str;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Identity {
   pub key: PublicKey,
   pub comment: String,
@@ -45,7 +48,7 @@ where
     for _ in 0..n {
       let raw_key = reader.read_string()?;
       let raw_comment = reader.read_string()?;
-      let key = PublicKey::parse_raw(raw_key)?;
+      let key = PublicKey::parse_raw_or_unknown(raw_key)?;
       let comment = str::from_utf8(raw_comment)?.to_string();
 
       identities.push(Identity { key, comment })
@@ -54,23 +57,45 @@ where
     Ok(identities)
   }
 
+  /// Sign with the most preferred algorithm of the key, i.e. `rsa-sha2-512` for RSA keys.
+  ///
+  /// Signatures of keys with an unknown type can not be decoded, use `sign_request_raw` for those.
   pub fn sign_request(&mut self, key: &PublicKey, data: &[u8]) -> Result<Signature> {
+    match key.signature_hashes().first() {
+      Some(hash) => self.sign_request_with(key, data, *hash),
+      None => Err(Error::UnsupportedKeyType(key.algorithm().to_string())),
+    }
+  }
+
+  /// Sign with any key, including ones of unknown type, and return the signature as wire blob. The agent
+  /// picks the algorithm.
+  pub fn sign_request_raw(&mut self, key: &PublicKey, data: &[u8]) -> Result<Vec<u8>> {
+    self.send_sign_request(key, data, 0)
+  }
+
+  /// Sign with a specific algorithm, failing if the agent answers with a different one.
+  pub fn sign_request_with(&mut self, key: &PublicKey, data: &[u8], hash: SignatureHash) -> Result<Signature> {
+    let flags = match hash {
+      SignatureHash::RsaSha256 => 2,
+      SignatureHash::RsaSha512 => 4,
+      _ => 0,
+    };
+    let raw_signature = self.send_sign_request(key, data, flags)?;
+    let (signature, _) = Signature::parse_raw(&raw_signature)?;
+
+    if signature.hash != hash {
+      return Err(Error::InvalidSignature);
+    }
+    Ok(signature)
+  }
+
+  fn send_sign_request(&mut self, key: &PublicKey, data: &[u8], flags: u32) -> Result<Vec<u8>> {
     let mut msg = MessageBuilder::new();
 
     msg.write_u8(SIGN_REQUEST);
     msg.write_string(&key.to_ssh_key());
     msg.write_string(data);
-    match key {
-      PublicKey::Rsa {
-        preferred_hash: SignatureHash::RsaSha256,
-        ..
-      } => msg.write_u32(2),
-      PublicKey::Rsa {
-        preferred_hash: SignatureHash::RsaSha512,
-        ..
-      } => msg.write_u32(4),
-      _ => msg.write_u32(0),
-    }
+    msg.write_u32(flags);
     self.stream.write_all(msg.payload())?;
 
     let response = self.read_response()?;
@@ -79,10 +104,8 @@ where
     if reader.read_u8()? != SIGN_RESPONSE {
       return Err(Error::RequestFailure);
     }
-    let raw_signature = reader.read_string()?;
-    let (signature, _) = Signature::parse_raw(raw_signature)?;
 
-    Ok(signature)
+    Ok(reader.read_string()?.to_vec())
   }
 
   pub fn remove_all_identities(&mut self) -> Result<()> {
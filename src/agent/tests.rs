@@ -1,11 +1,18 @@
 use crate::agent::client::AgentClient;
+use crate::agent::msg::{MessageBuilder, IDENTITIES_ANSWER, REQUEST_IDENTITIES, SIGN_REQUEST, SIGN_RESPONSE};
+use crate::cert_authority::{AgentSigner, CertRequest, CertificateAuthority, IssuancePolicy};
+use crate::cert_builder::CertificateBuilder;
+use crate::cert_validator::CertValidator;
+use crate::certificate::{CertType, Certificate};
+use crate::encoding::Writer;
 use crate::error::{Error, Result};
 use crate::public::PublicKey;
-use crate::signature::Signature;
+use crate::signature::{Signature, SignatureHash, VerifyOptions};
 use rand::RngCore;
 use spectral::prelude::*;
 use std::env;
 use std::fs;
+use std::io::{self, Read, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
@@ -131,6 +138,62 @@ fn test_rsa_signature() {
   }
 }
 
+#[test]
+fn test_rsa_signature_hash_choice() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  test_agent.add_fixture_key("unencrypted_rsa").unwrap();
+
+  let key = read_pub_key("unencrypted_rsa.pub").unwrap();
+  let hash = key
+    .best_signature_hash(&[SignatureHash::RsaSha1, SignatureHash::RsaSha256])
+    .unwrap();
+
+  assert_that(&hash).is_equal_to(SignatureHash::RsaSha256);
+
+  let signature = client.sign_request_with(&key, b"data", hash).unwrap();
+
+  assert_that(&signature.hash).is_equal_to(SignatureHash::RsaSha256);
+  signature.verify(&key, b"data").unwrap();
+
+  let signature = client.sign_request(&key, b"data").unwrap();
+
+  assert_that(&signature.hash).is_equal_to(SignatureHash::RsaSha512);
+}
+
+#[test]
+fn test_rsa_sha1_signature() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  test_agent.add_fixture_key("unencrypted_rsa").unwrap();
+
+  let key = read_pub_key("unencrypted_rsa.pub").unwrap();
+  let legacy = VerifyOptions {
+    allow_sha1: true,
+    ..Default::default()
+  };
+  let mut rng = rand::thread_rng();
+
+  for _ in 0..10 {
+    let mut data = [0u8; 64];
+    rng.fill_bytes(&mut data);
+
+    let signature = client.sign_request_with(&key, &data, SignatureHash::RsaSha1).unwrap();
+
+    assert_that(&signature.hash).is_equal_to(SignatureHash::RsaSha1);
+    assert_that(&signature.verify(&key, &data).is_err()).is_true();
+
+    signature.verify_with(&key, &data, &legacy).unwrap();
+
+    data[0] ^= 1;
+    assert_that(&signature.verify_with(&key, &data, &legacy).is_err()).is_true();
+  }
+}
+
 #[test]
 fn test_ed25519_signature() {
   let test_agent = TestAgent::spawn().unwrap();
@@ -164,6 +227,102 @@ fn test_ed25519_signature() {
   }
 }
 
+#[test]
+fn test_certificate_identity() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  // ssh-add loads the certificate next to the key as well
+  test_agent.add_fixture_key("certs/ed25519").unwrap();
+
+  let identities = client.request_identities().unwrap();
+
+  assert_that(&identities).has_length(2);
+
+  let key = &identities
+    .iter()
+    .find(|identity| identity.key.algorithm() == "ssh-ed25519-cert-v01@openssh.com")
+    .unwrap()
+    .key;
+  let cert = match key {
+    PublicKey::Certificate(cert) => cert,
+    _ => panic!("Not a certificate"),
+  };
+  let ref_key = read_pub_key("unencrypted_ed25519.pub").unwrap();
+
+  assert_that(&cert.key).is_equal_to(&ref_key);
+  assert_that(&cert.key_id.as_str()).is_equal_to("test-user");
+
+  let signature = client.sign_request(key, b"certificate").unwrap();
+
+  signature.verify(key, b"certificate").unwrap();
+  signature.verify(&ref_key, b"certificate").unwrap();
+}
+
+#[test]
+fn test_sign_certificate() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  test_agent.add_fixture_key("certs/ca_ed25519").unwrap();
+  test_agent.add_fixture_key("certs/ca_rsa").unwrap();
+
+  for (ca_name, hash) in [
+    ("certs/ca_ed25519.pub", SignatureHash::Ed25519),
+    ("certs/ca_rsa.pub", SignatureHash::RsaSha512),
+  ]
+  .iter()
+  {
+    let ca_key = read_pub_key(ca_name).unwrap();
+    let cert = CertificateBuilder::new(read_pub_key("unencrypted_ecdsa384.pub").unwrap(), CertType::User)
+      .key_id("test-agent")
+      .principal("alice")
+      .valid_for(time::Duration::from_secs(60))
+      .sign_with_agent(&mut client, &ca_key)
+      .unwrap();
+
+    assert_that(&cert.signature.hash).is_equal_to(hash);
+    CertValidator::new(CertType::User, &[ca_key])
+      .validate(&cert, "alice")
+      .unwrap();
+  }
+}
+
+#[test]
+fn test_certificate_authority() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let temp_dir = TempDir::new().unwrap();
+
+  test_agent.add_fixture_key("certs/ca_rsa").unwrap();
+
+  let ca_key = read_pub_key("certs/ca_rsa.pub").unwrap();
+  let signer = AgentSigner::new(AgentClient::connect(socket), ca_key.clone());
+  let policy = IssuancePolicy::new(time::Duration::from_secs(3600)).allow("ops", &["host.example.com"]);
+  let mut ca = CertificateAuthority::new(
+    signer,
+    policy,
+    temp_dir.path().join("serial"),
+    temp_dir.path().join("issued.log"),
+  );
+  let request = CertRequest::new(
+    "ops",
+    read_pub_key("unencrypted_ecdsa.pub").unwrap(),
+    CertType::Host,
+    &["host.example.com"],
+    time::Duration::from_secs(300),
+  );
+
+  let cert = Certificate::parse_pub(&ca.issue(&request).unwrap()).unwrap();
+
+  assert_that(&cert.serial).is_equal_to(1);
+  CertValidator::new(CertType::Host, &[ca_key])
+    .validate(&cert, "host.example.com")
+    .unwrap();
+}
+
 #[test]
 fn test_ecdsa_signature() {
   let test_agent = TestAgent::spawn().unwrap();
@@ -229,3 +388,160 @@ fn test_ecdsa384_signature() {
     signature2.verify(&ref_key, &data).unwrap();
   }
 }
+
+#[test]
+fn test_ecdsa521_signature() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  test_agent.add_fixture_key("unencrypted_ecdsa521").unwrap();
+
+  let identities = client.request_identities().unwrap();
+
+  assert_that(&identities).has_length(1);
+
+  let key = &identities.first().unwrap().key;
+  let ref_key = read_pub_key("unencrypted_ecdsa521.pub").unwrap();
+  let mut rng = rand::thread_rng();
+
+  for _ in 0..5 {
+    let mut data = [0u8; 64];
+    rng.fill_bytes(&mut data);
+
+    let signature = client.sign_request(key, &data).unwrap();
+    let sig_raw = signature.to_ssh_sig();
+    let (signature2, remaining) = Signature::parse_raw(&sig_raw).unwrap();
+
+    assert_that(&remaining.is_empty()).is_true();
+
+    signature.verify(key, &data).unwrap();
+    signature.verify(&ref_key, &data).unwrap();
+    signature2.verify(key, &data).unwrap();
+    signature2.verify(&ref_key, &data).unwrap();
+
+    data[0] ^= 1;
+    assert_that(&signature.verify(key, &data).is_err()).is_true();
+  }
+}
+
+#[cfg(feature = "legacy-dsa")]
+#[test]
+fn test_dsa_signature() {
+  let test_agent = TestAgent::spawn().unwrap();
+  let socket = UnixStream::connect(&test_agent.file_name).unwrap();
+  let mut client = AgentClient::connect(socket);
+
+  test_agent.add_fixture_key("unencrypted_dsa").unwrap();
+
+  let identities = client.request_identities().unwrap();
+
+  assert_that(&identities).has_length(1);
+
+  let key = &identities.first().unwrap().key;
+  let ref_key = read_pub_key("unencrypted_dsa.pub").unwrap();
+  let options = VerifyOptions::allowing(&[SignatureHash::Dss]);
+  let mut rng = rand::thread_rng();
+
+  for _ in 0..20 {
+    let mut data = [0u8; 64];
+    rng.fill_bytes(&mut data);
+
+    let signature = client.sign_request(key, &data).unwrap();
+    let sig_raw = signature.to_ssh_sig();
+    let (signature2, remaining) = Signature::parse_raw(&sig_raw).unwrap();
+
+    assert_that(&remaining.is_empty()).is_true();
+
+    match signature.verify(key, &data) {
+      Err(Error::AlgorithmNotAllowed(_)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    signature.verify_with(key, &data, &options).unwrap();
+    signature.verify_with(&ref_key, &data, &options).unwrap();
+    signature2.verify_with(key, &data, &options).unwrap();
+    signature2.verify_with(&ref_key, &data, &options).unwrap();
+
+    data[0] ^= 1;
+    assert_that(&signature.verify_with(key, &data, &options).is_err()).is_true();
+  }
+}
+
+/// Stream replaying canned agent responses and recording the requests.
+struct ReplayStream {
+  responses: io::Cursor<Vec<u8>>,
+  requests: Vec<u8>,
+}
+
+impl Read for ReplayStream {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    self.responses.read(buf)
+  }
+}
+
+impl Write for ReplayStream {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.requests.write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[test]
+fn test_unknown_identity() {
+  let known = read_pub_key("unencrypted_ed25519.pub").unwrap().to_ssh_key();
+  let mut unknown = Writer::new();
+  unknown.write_string(b"unknown-key@example.com");
+  unknown.write_string(b"opaque");
+
+  let mut identities = MessageBuilder::new();
+  identities.write_u8(IDENTITIES_ANSWER);
+  identities.write_u32(2);
+  identities.write_string(&unknown.buffer);
+  identities.write_string(b"unknown");
+  identities.write_string(&known);
+  identities.write_string(b"known");
+  let mut raw_signature = Writer::new();
+  raw_signature.write_string(b"unknown-sig@example.com");
+  raw_signature.write_string(b"opaque signature");
+  let mut signature = MessageBuilder::new();
+  signature.write_u8(SIGN_RESPONSE);
+  signature.write_string(&raw_signature.buffer);
+
+  let mut stream = ReplayStream {
+    responses: io::Cursor::new([identities.payload(), signature.payload()].concat()),
+    requests: vec![],
+  };
+  let mut client = AgentClient::connect(&mut stream);
+  let identities = client.request_identities().unwrap();
+
+  assert_that(&identities).has_length(2);
+  match &identities[0].key {
+    PublicKey::Unknown { algorithm, blob } => {
+      assert_that(&algorithm.as_str()).is_equal_to("unknown-key@example.com");
+      assert_that(blob).is_equal_to(&unknown.buffer);
+    }
+    _ => panic!("Not an unknown key"),
+  }
+  assert_that(&identities[1].key.to_ssh_key()).is_equal_to(&known);
+
+  match client.sign_request(&identities[0].key, b"data") {
+    Err(Error::UnsupportedKeyType(algorithm)) => {
+      assert_that(&algorithm.as_str()).is_equal_to("unknown-key@example.com")
+    }
+    other => panic!("Unexpected result: {:?}", other),
+  }
+  assert_that(&client.sign_request_raw(&identities[0].key, b"data").unwrap()).is_equal_to(&raw_signature.buffer);
+
+  let mut expected = MessageBuilder::new();
+  expected.write_u8(REQUEST_IDENTITIES);
+  let mut sign_request = MessageBuilder::new();
+  sign_request.write_u8(SIGN_REQUEST);
+  sign_request.write_string(&unknown.buffer);
+  sign_request.write_string(b"data");
+  sign_request.write_u32(0);
+
+  assert_that(&stream.requests).is_equal_to([expected.payload(), sign_request.payload()].concat());
+}
@@ -0,0 +1,544 @@
+use crate::error::{Error, Result};
+use crate::public::{PublicKey, PublicKeyLine};
+
+/// Effective restrictions of an `authorized_keys` entry, following the semantics of sshd.
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyOptions {
+  pub cert_authority: bool,
+  pub command: Option<String>,
+  pub environment: Vec<(String, String)>,
+  /// All `expiry-time` options, the earliest of them applies.
+  pub expiry_times: Vec<ExpiryTime>,
+  pub from: Option<Vec<String>>,
+  pub principals: Option<Vec<String>>,
+  pub permit_open: Vec<String>,
+  pub permit_listen: Vec<String>,
+  pub tunnel: Option<u32>,
+  pub restrict: bool,
+  pub permit_agent_forwarding: bool,
+  pub permit_port_forwarding: bool,
+  pub permit_pty: bool,
+  pub permit_user_rc: bool,
+  pub permit_x11_forwarding: bool,
+  pub no_touch_required: bool,
+  pub verify_required: bool,
+}
+
+impl Default for KeyOptions {
+  fn default() -> Self {
+    KeyOptions {
+      cert_authority: false,
+      command: None,
+      environment: vec![],
+      expiry_times: vec![],
+      from: None,
+      principals: None,
+      permit_open: vec![],
+      permit_listen: vec![],
+      tunnel: None,
+      restrict: false,
+      permit_agent_forwarding: true,
+      permit_port_forwarding: true,
+      permit_pty: true,
+      permit_user_rc: true,
+      permit_x11_forwarding: true,
+      no_touch_required: false,
+      verify_required: false,
+    }
+  }
+}
+
+impl KeyOptions {
+  /// Unix timestamp at which the key expires, with local times shifted by the seconds `utc_offset` returns for
+  /// them, e.g. the local UTC offset at that time. Like sshd, the earliest expiry time wins.
+  pub fn expiry_time<F: Fn(u64) -> i64>(&self, utc_offset: F) -> Option<u64> {
+    self.expiry_times.iter().map(|time| time.timestamp(&utc_offset)).min()
+  }
+
+  pub fn parse(options: &str) -> std::result::Result<KeyOptions, String> {
+    let mut result = KeyOptions::default();
+    let mut chars = options.chars().peekable();
+
+    loop {
+      let mut name = String::new();
+      while let Some(c) = chars.peek() {
+        if *c == '=' || *c == ',' {
+          break;
+        }
+        name.push(*c);
+        chars.next();
+      }
+      let value = if chars.peek() == Some(&'=') {
+        chars.next();
+        if chars.next() != Some('"') {
+          return Err(format!("missing quotes for option {}", name));
+        }
+        let mut value = String::new();
+        loop {
+          match chars.next() {
+            Some('\\') if chars.peek() == Some(&'"') => {
+              value.push('"');
+              chars.next();
+            }
+            Some('"') => break,
+            Some(c) => value.push(c),
+            None => return Err(format!("unterminated quotes for option {}", name)),
+          }
+        }
+        Some(value)
+      } else {
+        None
+      };
+
+      result.apply(&name, value)?;
+
+      match chars.next() {
+        Some(',') => continue,
+        None => break,
+        Some(c) => return Err(format!("unexpected character '{}' after option {}", c, name)),
+      }
+    }
+
+    Ok(result)
+  }
+
+  fn apply(&mut self, name: &str, value: Option<String>) -> std::result::Result<(), String> {
+    let flag = |value: &Option<String>| match value {
+      None => Ok(()),
+      Some(_) => Err(format!("option {} does not take a value", name)),
+    };
+    let required = |value: Option<String>| value.ok_or_else(|| format!("option {} requires a value", name));
+
+    match name.to_ascii_lowercase().as_str() {
+      "restrict" => {
+        flag(&value)?;
+        self.restrict = true;
+        self.permit_agent_forwarding = false;
+        self.permit_port_forwarding = false;
+        self.permit_pty = false;
+        self.permit_user_rc = false;
+        self.permit_x11_forwarding = false;
+      }
+      "cert-authority" => {
+        flag(&value)?;
+        self.cert_authority = true;
+      }
+      "agent-forwarding" => {
+        flag(&value)?;
+        self.permit_agent_forwarding = true;
+      }
+      "no-agent-forwarding" => {
+        flag(&value)?;
+        self.permit_agent_forwarding = false;
+      }
+      "port-forwarding" => {
+        flag(&value)?;
+        self.permit_port_forwarding = true;
+      }
+      "no-port-forwarding" => {
+        flag(&value)?;
+        self.permit_port_forwarding = false;
+      }
+      "pty" => {
+        flag(&value)?;
+        self.permit_pty = true;
+      }
+      "no-pty" => {
+        flag(&value)?;
+        self.permit_pty = false;
+      }
+      "user-rc" => {
+        flag(&value)?;
+        self.permit_user_rc = true;
+      }
+      "no-user-rc" => {
+        flag(&value)?;
+        self.permit_user_rc = false;
+      }
+      "x11-forwarding" => {
+        flag(&value)?;
+        self.permit_x11_forwarding = true;
+      }
+      "no-x11-forwarding" => {
+        flag(&value)?;
+        self.permit_x11_forwarding = false;
+      }
+      "no-touch-required" => {
+        flag(&value)?;
+        self.no_touch_required = true;
+      }
+      "verify-required" => {
+        flag(&value)?;
+        self.verify_required = true;
+      }
+      "command" => {
+        if self.command.is_some() {
+          return Err("duplicate command option".to_string());
+        }
+        self.command = Some(required(value)?);
+      }
+      "environment" => {
+        let value = required(value)?;
+        match value.find('=') {
+          Some(idx) if idx > 0 => self
+            .environment
+            .push((value[..idx].to_string(), value[idx + 1..].to_string())),
+          _ => return Err(format!("invalid environment string: {}", value)),
+        }
+      }
+      "expiry-time" => {
+        let value = required(value)?;
+        let time = ExpiryTime::parse(&value).ok_or_else(|| format!("invalid expiry-time: {}", value))?;
+        self.expiry_times.push(time);
+      }
+      "from" => {
+        if self.from.is_some() {
+          return Err("duplicate from option".to_string());
+        }
+        self.from = Some(split_list(&required(value)?));
+      }
+      "principals" => {
+        if self.principals.is_some() {
+          return Err("duplicate principals option".to_string());
+        }
+        self.principals = Some(split_list(&required(value)?));
+      }
+      "permitopen" => self.permit_open.push(required(value)?),
+      "permitlisten" => self.permit_listen.push(required(value)?),
+      "tunnel" => {
+        let value = required(value)?;
+        self.tunnel = Some(value.parse().map_err(|_| format!("invalid tunnel number: {}", value))?);
+      }
+      _ => return Err(format!("unknown option {}", name)),
+    }
+
+    Ok(())
+  }
+}
+
+/// Value of an `expiry-time` option. sshd reads values with a `Z` suffix as UTC and all others as local time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ExpiryTime {
+  /// Seconds since 1970-01-01 of the date and time as written, i.e. the unix timestamp if `utc` is set.
+  pub secs: u64,
+  pub utc: bool,
+}
+
+impl ExpiryTime {
+  pub fn parse(spec: &str) -> Option<ExpiryTime> {
+    Some(ExpiryTime {
+      secs: parse_timespec(spec)?,
+      utc: spec.ends_with('Z'),
+    })
+  }
+
+  /// Unix timestamp of this time, with a local time shifted by the seconds `utc_offset` returns for it.
+  pub fn timestamp<F: Fn(u64) -> i64>(&self, utc_offset: F) -> u64 {
+    if self.utc {
+      self.secs
+    } else {
+      (i128::from(self.secs) - i128::from(utc_offset(self.secs))).max(0) as u64
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct AuthorizedKey {
+  /// 1-based line number in the file the entry was read from.
+  pub line: usize,
+  pub options: KeyOptions,
+  pub key: PublicKey,
+  pub comment: Option<String>,
+}
+
+impl AuthorizedKey {
+  pub fn parse_line(line_number: usize, line: &str) -> Result<AuthorizedKey> {
+    let line = line.trim();
+    let invalid = |msg: String| Error::InvalidLine(line_number, msg);
+
+    let (options, key_part) = if starts_with_key_type(line) {
+      (KeyOptions::default(), line)
+    } else {
+      let end = options_end(line).ok_or_else(|| invalid("unterminated quotes in options".to_string()))?;
+      (
+        KeyOptions::parse(&line[..end]).map_err(invalid)?,
+        line[end..].trim_start(),
+      )
+    };
+    let PublicKeyLine { key, comment, .. } =
+      PublicKeyLine::parse_or_unknown(key_part).map_err(|e| invalid(format!("{}", e)))?;
+
+    Ok(AuthorizedKey {
+      line: line_number,
+      options,
+      key,
+      comment,
+    })
+  }
+}
+
+#[derive(Debug)]
+pub struct AuthorizedKeys {
+  pub entries: Vec<AuthorizedKey>,
+  /// Lines that were skipped because they could not be parsed, as `Error::InvalidLine`.
+  pub errors: Vec<Error>,
+}
+
+impl AuthorizedKeys {
+  /// Parse all entries of an `authorized_keys` file, skipping blank lines and comments. Like sshd, lines that
+  /// can not be parsed are skipped as well, keys of unknown type are kept as `PublicKey::Unknown`.
+  pub fn parse(content: &str) -> AuthorizedKeys {
+    let mut authorized_keys = AuthorizedKeys {
+      entries: Vec::new(),
+      errors: Vec::new(),
+    };
+
+    for (idx, line) in content.lines().enumerate() {
+      if is_blank_or_comment(line) {
+        continue;
+      }
+      match AuthorizedKey::parse_line(idx + 1, line) {
+        Ok(entry) => authorized_keys.entries.push(entry),
+        Err(err) => authorized_keys.errors.push(err),
+      }
+    }
+
+    authorized_keys
+  }
+}
+
+pub(crate) fn is_blank_or_comment(line: &str) -> bool {
+  let line = line.trim_start();
+  line.is_empty() || line.starts_with('#')
+}
+
+fn starts_with_key_type(line: &str) -> bool {
+  let first = line.split([' ', '\t']).next().unwrap_or("");
+  ["ssh-", "ecdsa-sha2-", "rsa-sha2-", "sk-"]
+    .iter()
+    .any(|prefix| first.starts_with(prefix))
+    && !first.contains('=')
+}
+
+/// Find the end of the options field, i.e. the first whitespace outside of quotes.
+fn options_end(line: &str) -> Option<usize> {
+  let mut in_quotes = false;
+  let mut escaped = false;
+
+  for (idx, c) in line.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if in_quotes => escaped = true,
+      '"' => in_quotes = !in_quotes,
+      ' ' | '\t' if !in_quotes => return Some(idx),
+      _ => (),
+    }
+  }
+  if in_quotes {
+    None
+  } else {
+    Some(line.len())
+  }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+  value.split(',').map(|s| s.trim().to_string()).collect()
+}
+
+/// Parse a `YYYYMMDD[HHMM[SS]]` time specification (optionally suffixed with `Z`) as UTC to a unix timestamp.
+pub(crate) fn parse_timespec(spec: &str) -> Option<u64> {
+  let spec = spec.strip_suffix('Z').unwrap_or(spec);
+  if !spec.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  let num = |range: std::ops::Range<usize>| spec[range].parse::<u64>().ok();
+  let (hour, minute, second) = match spec.len() {
+    8 => (0, 0, 0),
+    12 => (num(8..10)?, num(10..12)?, 0),
+    14 => (num(8..10)?, num(10..12)?, num(12..14)?),
+    _ => return None,
+  };
+  let (year, month, day) = (num(0..4)?, num(4..6)?, num(6..8)?);
+  if year < 1970 || !(1..=12).contains(&month) || hour > 23 || minute > 59 || second > 59 {
+    return None;
+  }
+  if day < 1 || day > days_in_month(year, month) {
+    return None;
+  }
+
+  Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+// `u64::is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn days_in_month(year: u64, month: u64) -> u64 {
+  match month {
+    2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+// Days since 1970-01-01 of a date in the proleptic gregorian calendar
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year / 400;
+  let year_of_era = year - era * 400;
+  let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+  era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn fixture_key(name: &str) -> String {
+    let line = fs::read_to_string(format!("fixtures/{}", name)).unwrap();
+    let parts: Vec<&str> = line.split(' ').collect();
+    format!("{} {}", parts[0], parts[1])
+  }
+
+  #[test]
+  fn parse_plain_and_options() {
+    let ed25519 = fixture_key("unencrypted_ed25519.pub");
+    let rsa = fixture_key("unencrypted_rsa.pub");
+    let content = format!(
+      "# comment\n\n{} first key\nrestrict,pty,command=\"echo \\\"hi there\\\"\",from=\"10.0.0.0/8,!10.1.2.3\" {} second\n\
+       environment=\"FOO=bar baz\",expiry-time=\"20300101\",principals=\"alice,bob\",no-pty,cert-authority {}\n",
+      ed25519, rsa, ed25519
+    );
+
+    let keys = AuthorizedKeys::parse(&content).entries;
+
+    assert_eq!(keys.len(), 3);
+    assert_eq!(keys[0].line, 3);
+    assert_eq!(keys[0].options, KeyOptions::default());
+    assert_eq!(keys[0].comment.as_deref(), Some("first key"));
+    match keys[0].key {
+      PublicKey::Ed25519(_) => (),
+      _ => panic!("Not an ed25519 key"),
+    }
+
+    let options = &keys[1].options;
+    assert_eq!(keys[1].line, 4);
+    assert!(options.restrict);
+    assert!(options.permit_pty);
+    assert!(!options.permit_port_forwarding);
+    assert!(!options.permit_agent_forwarding);
+    assert_eq!(options.command.as_deref(), Some("echo \"hi there\""));
+    assert_eq!(
+      options.from,
+      Some(vec!["10.0.0.0/8".to_string(), "!10.1.2.3".to_string()])
+    );
+    assert_eq!(keys[1].comment.as_deref(), Some("second"));
+
+    let options = &keys[2].options;
+    assert_eq!(options.environment, vec![("FOO".to_string(), "bar baz".to_string())]);
+    assert_eq!(
+      options.expiry_times,
+      vec![ExpiryTime {
+        secs: 1_893_456_000,
+        utc: false
+      }]
+    );
+    assert_eq!(options.expiry_time(|_| 3600), Some(1_893_452_400));
+    assert_eq!(options.principals, Some(vec!["alice".to_string(), "bob".to_string()]));
+    assert!(!options.permit_pty);
+    assert!(options.cert_authority);
+    assert_eq!(keys[2].comment, None);
+  }
+
+  #[test]
+  fn security_keys() {
+    let content = format!(
+      "{}
+no-touch-required {}
+",
+      fixture_key("sk_ecdsa.pub"),
+      fixture_key("sk_ed25519.pub")
+    );
+
+    let keys = AuthorizedKeys::parse(&content).entries;
+
+    match keys[0].key {
+      PublicKey::SkEcdsaP256 { .. } => (),
+      _ => panic!("Not an sk-ecdsa key"),
+    }
+    assert_eq!(keys[0].options, KeyOptions::default());
+    assert!(keys[1].options.no_touch_required);
+  }
+
+  #[test]
+  fn reports_line_numbers() {
+    let ed25519 = fixture_key("unencrypted_ed25519.pub");
+    let content = format!("{}\n# ok\nno-pty,bogus {}\n", ed25519, ed25519);
+
+    let authorized_keys = AuthorizedKeys::parse(&content);
+    assert_eq!(authorized_keys.entries.len(), 1);
+    match authorized_keys.errors.as_slice() {
+      [Error::InvalidLine(3, _)] => (),
+      other => panic!("Unexpected errors: {:?}", other),
+    }
+    match AuthorizedKey::parse_line(7, &format!("command=\"unterminated {}", ed25519)) {
+      Err(Error::InvalidLine(7, _)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn skip_invalid_lines() {
+    let ed25519 = fixture_key("unencrypted_ed25519.pub");
+    let mut unknown = crate::encoding::Writer::new();
+    unknown.write_string(b"ssh-vendor@example.com");
+    unknown.write_string(b"opaque");
+    let content = format!(
+      "ssh-vendor@example.com {} vendor\nssh-ed25519 !!invalid!!\nno-pty {}\n",
+      base64::encode(&unknown.buffer),
+      ed25519
+    );
+
+    let authorized_keys = AuthorizedKeys::parse(&content);
+
+    assert_eq!(authorized_keys.entries.len(), 2);
+    match &authorized_keys.entries[0].key {
+      PublicKey::Unknown { algorithm, .. } => assert_eq!(algorithm, "ssh-vendor@example.com"),
+      other => panic!("Not an unknown key: {:?}", other),
+    }
+    assert_eq!(authorized_keys.entries[1].line, 3);
+    assert!(!authorized_keys.entries[1].options.permit_pty);
+    match authorized_keys.errors.as_slice() {
+      [Error::InvalidLine(2, _)] => (),
+      other => panic!("Unexpected errors: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn expiry_times() {
+    let options = KeyOptions::parse("expiry-time=\"20300101\",expiry-time=\"20300101003000Z\"").unwrap();
+
+    assert_eq!(options.expiry_time(|_| 0), Some(1_893_456_000));
+    // 2030-01-01 00:00 at UTC-1 is after 00:30 UTC
+    assert_eq!(options.expiry_time(|_| -3600), Some(1_893_457_800));
+    assert_eq!(KeyOptions::default().expiry_time(|_| 0), None);
+  }
+
+  #[test]
+  fn timespec() {
+    assert_eq!(parse_timespec("19700101"), Some(0));
+    assert_eq!(parse_timespec("20200229123456Z"), Some(1_582_979_696));
+    assert_eq!(parse_timespec("202002291234"), Some(1_582_979_640));
+    assert_eq!(parse_timespec("2020022"), None);
+    assert_eq!(parse_timespec("20201301"), None);
+    assert_eq!(parse_timespec("20250231"), None);
+    assert_eq!(parse_timespec("20250431"), None);
+    assert_eq!(parse_timespec("20250229"), None);
+    assert_eq!(parse_timespec("21000229"), None);
+    assert_eq!(parse_timespec("20000229"), Some(951_782_400));
+    assert_eq!(parse_timespec("20251231"), Some(1_767_139_200));
+    assert_eq!(parse_timespec("20250100"), None);
+  }
+}
@@ -0,0 +1,634 @@
+use crate::agent::AgentClient;
+use crate::cert_builder::{CertificateBuilder, DEFAULT_EXTENSIONS};
+use crate::cert_listing::format_time;
+use crate::cert_options::Cidr;
+use crate::cert_validator::Clock;
+use crate::certificate::{CertType, Certificate};
+use crate::error::{Error, Result};
+use crate::policy::KeyPolicy;
+#[cfg(feature = "with-private")]
+use crate::private::KeyPair;
+use crate::public::PublicKey;
+use crate::signature::Signature;
+use fs2::FileExt;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Holder of a CA key that can sign certificates.
+pub trait CaSigner {
+  fn public_key(&self) -> Result<PublicKey>;
+  fn sign(&mut self, data: &[u8]) -> Result<Signature>;
+}
+
+/// A CA key held by an ssh-agent.
+pub struct AgentSigner<S> {
+  client: AgentClient<S>,
+  key: PublicKey,
+}
+
+impl<S> AgentSigner<S>
+where
+  S: Read + Write,
+{
+  pub fn new(client: AgentClient<S>, key: PublicKey) -> AgentSigner<S> {
+    AgentSigner { client, key }
+  }
+}
+
+impl<S> CaSigner for AgentSigner<S>
+where
+  S: Read + Write,
+{
+  fn public_key(&self) -> Result<PublicKey> {
+    Ok(self.key.clone())
+  }
+
+  fn sign(&mut self, data: &[u8]) -> Result<Signature> {
+    self.client.sign_request(&self.key, data)
+  }
+}
+
+#[cfg(feature = "with-private")]
+impl CaSigner for KeyPair {
+  fn public_key(&self) -> Result<PublicKey> {
+    KeyPair::public_key(self)
+  }
+
+  fn sign(&mut self, data: &[u8]) -> Result<Signature> {
+    KeyPair::sign(self, data)
+  }
+}
+
+/// Rules every certificate request has to satisfy, see `CertificateAuthority::issue`.
+#[derive(Debug, Clone)]
+pub struct IssuancePolicy {
+  pub max_validity: Duration,
+  /// Principals each requester may get certificates for. Unknown requesters get nothing.
+  pub allowed_principals: HashMap<String, HashSet<String>>,
+  /// Extensions requests may ask for, by default the `ssh-keygen` defaults.
+  pub allowed_extensions: HashSet<String>,
+  /// Commands requests may restrict user certificates to with `force-command`, by default none.
+  pub allowed_force_commands: HashSet<String>,
+  /// Extensions added to every user certificate on top of the requested ones.
+  pub mandatory_extensions: Vec<String>,
+  /// Added as `source-address` critical option to every user certificate.
+  pub source_address: Option<Vec<Cidr>>,
+  /// Admission rules for the keys to certify.
+  pub key_policy: KeyPolicy,
+}
+
+impl IssuancePolicy {
+  pub fn new(max_validity: Duration) -> IssuancePolicy {
+    IssuancePolicy {
+      max_validity,
+      allowed_principals: HashMap::new(),
+      allowed_extensions: DEFAULT_EXTENSIONS.iter().map(|name| name.to_string()).collect(),
+      allowed_force_commands: HashSet::new(),
+      mandatory_extensions: Vec::new(),
+      source_address: None,
+      key_policy: KeyPolicy::default(),
+    }
+  }
+
+  /// Allow `requester` to request certificates for `principals`.
+  pub fn allow(mut self, requester: &str, principals: &[&str]) -> Self {
+    self
+      .allowed_principals
+      .entry(requester.to_string())
+      .or_default()
+      .extend(principals.iter().map(|principal| principal.to_string()));
+    self
+  }
+}
+
+/// A request for a certificate. The key id of the certificate is the requester.
+#[derive(Debug, Clone)]
+pub struct CertRequest {
+  pub requester: String,
+  pub key: PublicKey,
+  pub cert_type: CertType,
+  pub principals: Vec<String>,
+  pub validity: Duration,
+  /// Requested extensions, e.g. `permit-pty`. `CertRequest::new` asks for the `ssh-keygen` defaults.
+  pub extensions: Vec<String>,
+  /// Only for user certificates, OpenSSH refuses host certificates with critical options.
+  pub force_command: Option<String>,
+}
+
+impl CertRequest {
+  pub fn new(requester: &str, key: PublicKey, cert_type: CertType, principals: &[&str], validity: Duration) -> Self {
+    let extensions = match cert_type {
+      CertType::User => DEFAULT_EXTENSIONS.iter().map(|name| name.to_string()).collect(),
+      CertType::Host => Vec::new(),
+    };
+
+    CertRequest {
+      requester: requester.to_string(),
+      key,
+      cert_type,
+      principals: principals.iter().map(|principal| principal.to_string()).collect(),
+      validity,
+      extensions,
+      force_command: None,
+    }
+  }
+}
+
+/// Why a `CertificateAuthority` refused to issue a certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuanceRejection {
+  UnknownRequester(String),
+  NoPrincipals,
+  PrincipalNotAllowed(String),
+  ExtensionNotAllowed(String),
+  /// A `force-command` outside the policy, or any for a host certificate.
+  ForceCommandNotAllowed(String),
+  ValidityTooLong {
+    requested: Duration,
+    max: Duration,
+  },
+  /// A validity of less than a second, the certificate would never be valid.
+  EmptyValidity,
+  /// An empty value or one with control characters, or a principal containing a comma.
+  InvalidCharacters(String),
+}
+
+impl fmt::Display for IssuanceRejection {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      IssuanceRejection::UnknownRequester(requester) => write!(f, "unknown requester {}", requester),
+      IssuanceRejection::NoPrincipals => write!(f, "no principals requested"),
+      IssuanceRejection::PrincipalNotAllowed(principal) => write!(f, "principal {} not allowed", principal),
+      IssuanceRejection::ExtensionNotAllowed(extension) => write!(f, "extension {} not allowed", extension),
+      IssuanceRejection::ForceCommandNotAllowed(command) => write!(f, "force-command {:?} not allowed", command),
+      IssuanceRejection::ValidityTooLong { requested, max } => write!(
+        f,
+        "validity of {}s exceeds the maximum of {}s",
+        requested.as_secs(),
+        max.as_secs()
+      ),
+      IssuanceRejection::EmptyValidity => write!(f, "empty validity period"),
+      IssuanceRejection::InvalidCharacters(value) => write!(f, "invalid characters in {:?}", value),
+    }
+  }
+}
+
+/// Issues certificates according to an `IssuancePolicy`, like a scripted `ssh-keygen -s` would.
+///
+/// Serial numbers are strictly increasing and persisted in `serial_file` before a certificate is signed, so
+/// they are never reused, even if signing fails. Every issued certificate is appended to `log_file`. Instances
+/// sharing the files, also in other processes, reserve serials under an exclusive lock on `<serial_file>.lock`.
+pub struct CertificateAuthority<S> {
+  signer: S,
+  pub policy: IssuancePolicy,
+  serial_file: PathBuf,
+  log_file: PathBuf,
+  /// Current time, replaceable for tests.
+  pub clock: Clock,
+}
+
+impl<S> CertificateAuthority<S>
+where
+  S: CaSigner,
+{
+  pub fn new<P: Into<PathBuf>>(signer: S, policy: IssuancePolicy, serial_file: P, log_file: P) -> Self {
+    CertificateAuthority {
+      signer,
+      policy,
+      serial_file: serial_file.into(),
+      log_file: log_file.into(),
+      clock: Clock::default(),
+    }
+  }
+
+  pub fn public_key(&self) -> Result<PublicKey> {
+    self.signer.public_key()
+  }
+
+  /// Check `request` against the policy and return the signed certificate as OpenSSH line.
+  pub fn issue(&mut self, request: &CertRequest) -> Result<String> {
+    self.check(request).map_err(Error::IssuanceRejected)?;
+    request.key.validate_with(&self.policy.key_policy)?;
+
+    let serial = self.next_serial()?;
+    let now = self.clock.now();
+    let mut builder = CertificateBuilder::new(request.key.clone(), request.cert_type)
+      .clear_extensions()
+      .serial(serial)
+      .key_id(&request.requester)
+      .validity(now, now.saturating_add(request.validity.as_secs()));
+    for principal in &request.principals {
+      builder = builder.principal(principal);
+    }
+    for extension in &request.extensions {
+      builder = builder.extension(extension, "");
+    }
+    if request.cert_type == CertType::User {
+      for extension in &self.policy.mandatory_extensions {
+        builder = builder.extension(extension, "");
+      }
+      if let Some(command) = &request.force_command {
+        builder = builder.critical_option("force-command", command);
+      }
+      if let Some(cidrs) = &self.policy.source_address {
+        let list = cidrs
+          .iter()
+          .map(|cidr| format!("{}", cidr))
+          .collect::<Vec<_>>()
+          .join(",");
+        builder = builder.critical_option("source-address", &list);
+      }
+    }
+
+    let ca_key = self.signer.public_key()?;
+    let signer = &mut self.signer;
+    let cert = builder.sign_with(&ca_key, |data| signer.sign(data))?;
+    self.log(&cert, now)?;
+
+    Ok(cert.to_openssh_line(None))
+  }
+
+  fn check(&self, request: &CertRequest) -> std::result::Result<(), IssuanceRejection> {
+    let values = request
+      .principals
+      .iter()
+      .chain(&request.force_command)
+      .chain(&request.extensions)
+      .chain(std::iter::once(&request.requester));
+    for value in values {
+      if value.is_empty() || value.chars().any(char::is_control) {
+        return Err(IssuanceRejection::InvalidCharacters(value.clone()));
+      }
+    }
+    // Principal lists are comma separated everywhere else, including the issuance log
+    if let Some(principal) = request.principals.iter().find(|principal| principal.contains(',')) {
+      return Err(IssuanceRejection::InvalidCharacters(principal.clone()));
+    }
+
+    let allowed = self
+      .policy
+      .allowed_principals
+      .get(&request.requester)
+      .ok_or_else(|| IssuanceRejection::UnknownRequester(request.requester.clone()))?;
+    if request.principals.is_empty() {
+      return Err(IssuanceRejection::NoPrincipals);
+    }
+    if let Some(principal) = request
+      .principals
+      .iter()
+      .find(|principal| !allowed.contains(*principal))
+    {
+      return Err(IssuanceRejection::PrincipalNotAllowed(principal.clone()));
+    }
+    if let Some(extension) = request
+      .extensions
+      .iter()
+      .find(|extension| !self.policy.allowed_extensions.contains(*extension))
+    {
+      return Err(IssuanceRejection::ExtensionNotAllowed(extension.clone()));
+    }
+    if let Some(command) = &request.force_command {
+      if request.cert_type == CertType::Host || !self.policy.allowed_force_commands.contains(command) {
+        return Err(IssuanceRejection::ForceCommandNotAllowed(command.clone()));
+      }
+    }
+    if request.validity.as_secs() == 0 {
+      return Err(IssuanceRejection::EmptyValidity);
+    }
+    if request.validity > self.policy.max_validity {
+      return Err(IssuanceRejection::ValidityTooLong {
+        requested: request.validity,
+        max: self.policy.max_validity,
+      });
+    }
+
+    Ok(())
+  }
+
+  /// Reserve the next serial, starting at 1 without a serial file.
+  fn next_serial(&self) -> Result<u64> {
+    // Held until the new serial is in place, the lock file itself is never removed
+    let lock_file = OpenOptions::new()
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(with_suffix(&self.serial_file, ".lock"))?;
+    lock_file.lock_exclusive()?;
+
+    let last = match fs::read_to_string(&self.serial_file) {
+      Ok(content) => content
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| Error::IO(format!("invalid serial file {}", self.serial_file.display())))?,
+      Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+      Err(err) => return Err(err.into()),
+    };
+    let serial = last
+      .checked_add(1)
+      .ok_or_else(|| Error::IO("serial numbers exhausted".to_string()))?;
+
+    // Write and rename, so that a crash never leaves a truncated serial file behind
+    let temp_file = with_suffix(&self.serial_file, ".tmp");
+    let mut file = fs::File::create(&temp_file)?;
+    file.write_all(format!("{}\n", serial).as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_file, &self.serial_file)?;
+
+    Ok(serial)
+  }
+
+  fn log(&self, cert: &Certificate, now: u64) -> Result<()> {
+    let line = format!(
+      "{} serial={} type={} key_id={:?} principals={} valid_before={} key={} ca={}\n",
+      format_time(i128::from(now)),
+      cert.serial,
+      cert.cert_type,
+      cert.key_id,
+      cert.valid_principals.join(","),
+      cert.valid_before,
+      cert.key.fingerprint_sha256(),
+      cert.signature_key.fingerprint_sha256()
+    );
+    let mut file = OpenOptions::new().append(true).create(true).open(&self.log_file)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_all()?;
+
+    Ok(())
+  }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut name = path.as_os_str().to_os_string();
+  name.push(suffix);
+  PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cert_validator::{CertValidator, Clock};
+  use crate::signature::SignatureHash;
+  use ring::rand::SystemRandom;
+  use ring::signature::{Ed25519KeyPair, KeyPair};
+  use std::thread;
+  use tempfile::TempDir;
+
+  struct TestSigner(Ed25519KeyPair);
+
+  impl CaSigner for TestSigner {
+    fn public_key(&self) -> Result<PublicKey> {
+      Ok(PublicKey::Ed25519(self.0.public_key().as_ref().to_vec()))
+    }
+
+    fn sign(&mut self, data: &[u8]) -> Result<Signature> {
+      Ok(Signature {
+        hash: SignatureHash::Ed25519,
+        signature: self.0.sign(data).as_ref().to_vec(),
+        security_key: None,
+      })
+    }
+  }
+
+  fn test_signer() -> TestSigner {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    TestSigner(Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap())
+  }
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  fn test_ca(dir: &TempDir) -> CertificateAuthority<TestSigner> {
+    let policy = IssuancePolicy::new(Duration::from_secs(3600))
+      .allow("alice", &["alice", "deploy"])
+      .allow("bob", &["bob"]);
+    let mut ca = CertificateAuthority::new(
+      test_signer(),
+      policy,
+      dir.path().join("serial"),
+      dir.path().join("issued.log"),
+    );
+    ca.clock = Clock::fixed(1_735_689_600);
+    ca
+  }
+
+  fn request(requester: &str, principals: &[&str]) -> CertRequest {
+    CertRequest::new(
+      requester,
+      read_pub_key("unencrypted_ed25519.pub"),
+      CertType::User,
+      principals,
+      Duration::from_secs(600),
+    )
+  }
+
+  fn rejection(result: Result<String>) -> IssuanceRejection {
+    match result {
+      Err(Error::IssuanceRejected(rejection)) => rejection,
+      other => panic!("Unexpected result {:?}", other),
+    }
+  }
+
+  #[test]
+  fn issue() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+    ca.policy.mandatory_extensions = vec!["permit-pty".to_string(), "no-touch-required".to_string()];
+    ca.policy.source_address = Some(vec!["10.0.0.0/8".parse().unwrap()]);
+    ca.policy
+      .allowed_force_commands
+      .insert("/usr/bin/deploy --all".to_string());
+    let mut req = request("alice", &["alice", "deploy"]);
+    req.extensions = vec!["permit-agent-forwarding".to_string()];
+    req.force_command = Some("/usr/bin/deploy --all".to_string());
+
+    let cert = Certificate::parse_pub(&ca.issue(&req).unwrap()).unwrap();
+
+    assert_eq!(cert.serial, 1);
+    assert_eq!(cert.key_id, "alice");
+    assert_eq!(cert.valid_principals, vec!["alice", "deploy"]);
+    assert_eq!(cert.valid_after, 1_735_689_600);
+    assert_eq!(cert.valid_before, 1_735_690_200);
+    assert_eq!(
+      cert
+        .extensions
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["no-touch-required", "permit-agent-forwarding", "permit-pty"]
+    );
+
+    let mut validator = CertValidator::new(CertType::User, &[ca.public_key().unwrap()]);
+    validator.clock = Clock::fixed(1_735_689_600);
+    let permissions = validator
+      .authorize(&cert, "deploy", "10.1.1.1".parse().unwrap())
+      .unwrap();
+    assert_eq!(permissions.force_command.as_deref(), Some("/usr/bin/deploy --all"));
+    assert!(validator
+      .authorize(&cert, "deploy", "192.168.0.1".parse().unwrap())
+      .is_err());
+  }
+
+  #[test]
+  fn host_cert_without_user_options() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+    ca.policy = ca.policy.clone().allow("provisioner", &["host.example.com"]);
+    ca.policy.source_address = Some(vec!["10.0.0.0/8".parse().unwrap()]);
+    ca.policy.mandatory_extensions = vec!["permit-pty".to_string()];
+    let req = CertRequest::new(
+      "provisioner",
+      read_pub_key("unencrypted_ed25519.pub"),
+      CertType::Host,
+      &["host.example.com"],
+      Duration::from_secs(600),
+    );
+
+    let cert = Certificate::parse_pub(&ca.issue(&req).unwrap()).unwrap();
+    assert!(cert.critical_options.is_empty());
+    assert!(cert.extensions.is_empty());
+
+    let mut validator = CertValidator::new(CertType::Host, &[ca.public_key().unwrap()]);
+    validator.clock = Clock::fixed(1_735_689_600);
+    assert!(validator.validate(&cert, "host.example.com").is_ok());
+  }
+
+  #[test]
+  fn serial_and_log() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+
+    assert!(ca.issue(&request("alice", &["alice"])).is_ok());
+    assert!(ca.issue(&request("bob", &["bob"])).is_ok());
+    // Rejected requests neither use a serial nor show up in the log
+    assert!(ca.issue(&request("bob", &["alice"])).is_err());
+
+    // A new instance continues where the last one stopped
+    let mut ca = test_ca(&dir);
+    let cert = Certificate::parse_pub(&ca.issue(&request("alice", &["deploy"])).unwrap()).unwrap();
+    assert_eq!(cert.serial, 3);
+    assert_eq!(fs::read_to_string(dir.path().join("serial")).unwrap(), "3\n");
+
+    let log = fs::read_to_string(dir.path().join("issued.log")).unwrap();
+    let lines = log.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("2025-01-01T00:00:00 serial=2 type=user key_id=\"bob\" principals=bob "));
+    assert!(lines[2].contains(&format!("key={}", cert.key.fingerprint_sha256())));
+  }
+
+  #[test]
+  fn concurrent_serials() {
+    let dir = TempDir::new().unwrap();
+    let threads = (0..4)
+      .map(|_| {
+        let ca = test_ca(&dir);
+        thread::spawn(move || (0..10).map(|_| ca.next_serial().unwrap()).collect::<Vec<_>>())
+      })
+      .collect::<Vec<_>>();
+    let mut serials = threads
+      .into_iter()
+      .flat_map(|thread| thread.join().unwrap())
+      .collect::<Vec<_>>();
+    serials.sort_unstable();
+
+    assert_eq!(serials, (1..=40).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn serial_file_named_tmp() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+    ca.serial_file = dir.path().join("serial.tmp");
+
+    assert_eq!(ca.next_serial().unwrap(), 1);
+    assert_eq!(ca.next_serial().unwrap(), 2);
+    assert_eq!(fs::read_to_string(dir.path().join("serial.tmp")).unwrap(), "2\n");
+  }
+
+  #[test]
+  fn reject_requests() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+
+    assert_eq!(
+      rejection(ca.issue(&request("mallory", &["mallory"]))),
+      IssuanceRejection::UnknownRequester("mallory".to_string())
+    );
+    assert_eq!(
+      rejection(ca.issue(&request("bob", &[]))),
+      IssuanceRejection::NoPrincipals
+    );
+    assert_eq!(
+      rejection(ca.issue(&request("bob", &["bob", "root"]))),
+      IssuanceRejection::PrincipalNotAllowed("root".to_string())
+    );
+    assert_eq!(
+      rejection(ca.issue(&request("bob\n", &["bob"]))),
+      IssuanceRejection::InvalidCharacters("bob\n".to_string())
+    );
+
+    let mut req = request("bob", &["bob"]);
+    req.validity = Duration::from_secs(7200);
+    assert_eq!(
+      rejection(ca.issue(&req)),
+      IssuanceRejection::ValidityTooLong {
+        requested: Duration::from_secs(7200),
+        max: Duration::from_secs(3600),
+      }
+    );
+
+    let mut req = request("bob", &["bob"]);
+    req.validity = Duration::from_millis(500);
+    assert_eq!(rejection(ca.issue(&req)), IssuanceRejection::EmptyValidity);
+
+    // Keys are checked against the key policy as well
+    let mut req = request("bob", &["bob"]);
+    req.key = read_pub_key("unencrypted_rsa.pub");
+    ca.policy.key_policy.min_rsa_bits = 4096;
+    assert!(ca.issue(&req).is_err());
+
+    assert!(!dir.path().join("serial").exists());
+  }
+
+  #[test]
+  fn reject_extensions_and_force_command() {
+    let dir = TempDir::new().unwrap();
+    let mut ca = test_ca(&dir);
+    ca.policy.allowed_force_commands.insert("/usr/bin/deploy".to_string());
+
+    let mut req = request("alice", &["alice"]);
+    req.extensions.push("no-touch-required".to_string());
+    assert_eq!(
+      rejection(ca.issue(&req)),
+      IssuanceRejection::ExtensionNotAllowed("no-touch-required".to_string())
+    );
+
+    let mut req = request("alice", &["alice"]);
+    req.force_command = Some("/bin/sh".to_string());
+    assert_eq!(
+      rejection(ca.issue(&req)),
+      IssuanceRejection::ForceCommandNotAllowed("/bin/sh".to_string())
+    );
+    req.force_command = Some("/usr/bin/deploy".to_string());
+    assert!(ca.issue(&req).is_ok());
+
+    // Host certificates cannot carry a force-command at all
+    ca.policy = ca.policy.clone().allow("alice", &["host.example.com"]);
+    let mut req = CertRequest::new(
+      "alice",
+      read_pub_key("unencrypted_ed25519.pub"),
+      CertType::Host,
+      &["host.example.com"],
+      Duration::from_secs(600),
+    );
+    req.force_command = Some("/usr/bin/deploy".to_string());
+    assert_eq!(
+      rejection(ca.issue(&req)),
+      IssuanceRejection::ForceCommandNotAllowed("/usr/bin/deploy".to_string())
+    );
+  }
+}
@@ -0,0 +1,322 @@
+use crate::agent::AgentClient;
+use crate::cert_validator::system_time;
+use crate::certificate::{CertType, Certificate};
+use crate::encoding::Writer;
+use crate::error::{Error, Result};
+#[cfg(feature = "with-private")]
+use crate::private::KeyPair;
+use crate::public::PublicKey;
+use crate::signature::{Signature, SignatureHash};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+/// Extensions `ssh-keygen -s` adds to user certificates unless told otherwise.
+pub const DEFAULT_EXTENSIONS: &[&str] = &[
+  "permit-X11-forwarding",
+  "permit-agent-forwarding",
+  "permit-port-forwarding",
+  "permit-pty",
+  "permit-user-rc",
+];
+
+const NONCE_LEN: usize = 32;
+
+/// Assembles and signs an OpenSSH certificate, like `ssh-keygen -s` does.
+///
+/// By default the certificate has serial 0, no principals, never expires and, for user certificates, carries
+/// the `DEFAULT_EXTENSIONS`.
+#[derive(Debug, Clone)]
+pub struct CertificateBuilder {
+  key: PublicKey,
+  cert_type: CertType,
+  serial: u64,
+  key_id: String,
+  principals: Vec<String>,
+  valid_after: u64,
+  valid_before: u64,
+  // Both have to be sorted by name on the wire
+  critical_options: BTreeMap<String, Vec<u8>>,
+  extensions: BTreeMap<String, Vec<u8>>,
+}
+
+impl CertificateBuilder {
+  pub fn new(key: PublicKey, cert_type: CertType) -> CertificateBuilder {
+    let extensions = match cert_type {
+      CertType::User => DEFAULT_EXTENSIONS
+        .iter()
+        .map(|name| (name.to_string(), Vec::new()))
+        .collect(),
+      CertType::Host => BTreeMap::new(),
+    };
+
+    CertificateBuilder {
+      key,
+      cert_type,
+      serial: 0,
+      key_id: String::new(),
+      principals: Vec::new(),
+      valid_after: 0,
+      valid_before: u64::MAX,
+      critical_options: BTreeMap::new(),
+      extensions,
+    }
+  }
+
+  pub fn serial(mut self, serial: u64) -> Self {
+    self.serial = serial;
+    self
+  }
+
+  pub fn key_id(mut self, key_id: &str) -> Self {
+    self.key_id = key_id.to_string();
+    self
+  }
+
+  pub fn principal(mut self, principal: &str) -> Self {
+    self.principals.push(principal.to_string());
+    self
+  }
+
+  /// Validity window in seconds since the epoch, `valid_before` is exclusive.
+  pub fn validity(mut self, valid_after: u64, valid_before: u64) -> Self {
+    self.valid_after = valid_after;
+    self.valid_before = valid_before;
+    self
+  }
+
+  /// Valid from now on for `duration`.
+  pub fn valid_for(self, duration: Duration) -> Self {
+    let now = system_time();
+    self.validity(now, now.saturating_add(duration.as_secs()))
+  }
+
+  /// Add or replace a critical option. An empty value is encoded as a flag without data.
+  ///
+  /// Only user certificates may have critical options, OpenSSH refuses host certificates with any.
+  pub fn critical_option(mut self, name: &str, value: &str) -> Self {
+    self.critical_options.insert(name.to_string(), option_data(value));
+    self
+  }
+
+  /// Add or replace an extension. An empty value is encoded as a flag without data.
+  pub fn extension(mut self, name: &str, value: &str) -> Self {
+    self.extensions.insert(name.to_string(), option_data(value));
+    self
+  }
+
+  /// Drop all extensions, including the defaults.
+  pub fn clear_extensions(mut self) -> Self {
+    self.extensions.clear();
+    self
+  }
+
+  /// Sign with a CA key held by an ssh-agent.
+  pub fn sign_with_agent<S: Read + Write>(
+    self,
+    client: &mut AgentClient<S>,
+    ca_key: &PublicKey,
+  ) -> Result<Certificate> {
+    self.sign_with(ca_key, |data| client.sign_request(ca_key, data))
+  }
+
+  /// Sign with a local CA key.
+  #[cfg(feature = "with-private")]
+  pub fn sign_with_key(self, ca_key: &KeyPair) -> Result<Certificate> {
+    self.sign_with(&ca_key.public_key()?, |data| ca_key.sign(data))
+  }
+
+  /// Sign with any signer for `ca_key`. The signature is checked against `ca_key` before it is accepted.
+  pub fn sign_with<F>(self, ca_key: &PublicKey, sign: F) -> Result<Certificate>
+  where
+    F: FnOnce(&[u8]) -> Result<Signature>,
+  {
+    for key in [ca_key, &self.key].iter() {
+      if let PublicKey::Certificate(_) | PublicKey::Unknown { .. } = key {
+        return Err(Error::UnsupportedKeyType(key.algorithm().to_string()));
+      }
+    }
+    if self.cert_type == CertType::Host && !self.critical_options.is_empty() {
+      return Err(Error::InvalidCertificate(
+        "host certificates cannot have critical options".to_string(),
+      ));
+    }
+
+    let mut nonce = vec![0u8; NONCE_LEN];
+    SystemRandom::new()
+      .fill(&mut nonce)
+      .map_err(|_| Error::IO("no random numbers available".to_string()))?;
+
+    let mut cert = Certificate {
+      nonce,
+      key: self.key,
+      serial: self.serial,
+      cert_type: self.cert_type,
+      key_id: self.key_id,
+      valid_principals: self.principals,
+      valid_after: self.valid_after,
+      valid_before: self.valid_before,
+      critical_options: self.critical_options.into_iter().collect(),
+      extensions: self.extensions.into_iter().collect(),
+      reserved: Vec::new(),
+      signature_key: ca_key.clone(),
+      // Placeholder, the signature is not part of the signed data
+      signature: Signature {
+        hash: SignatureHash::Ed25519,
+        signature: Vec::new(),
+        security_key: None,
+      },
+    };
+    let data = cert.signed_data();
+    let signature = sign(&data)?;
+
+    signature.verify(ca_key, &data)?;
+    cert.signature = signature;
+
+    Ok(cert)
+  }
+}
+
+fn option_data(value: &str) -> Vec<u8> {
+  if value.is_empty() {
+    return Vec::new();
+  }
+  let mut writer = Writer::new();
+  writer.write_string(value.as_bytes());
+  writer.buffer
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cert_validator::{CertValidator, Clock};
+  use ring::signature::{Ed25519KeyPair, KeyPair};
+  use std::fs;
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  fn generate_ca() -> (Ed25519KeyPair, PublicKey) {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let public = PublicKey::Ed25519(key_pair.public_key().as_ref().to_vec());
+
+    (key_pair, public)
+  }
+
+  fn sign_ed25519(key_pair: &Ed25519KeyPair, data: &[u8]) -> Result<Signature> {
+    Ok(Signature {
+      hash: SignatureHash::Ed25519,
+      signature: key_pair.sign(data).as_ref().to_vec(),
+      security_key: None,
+    })
+  }
+
+  #[test]
+  fn user_cert() {
+    let (key_pair, ca_key) = generate_ca();
+    let cert = CertificateBuilder::new(read_pub_key("unencrypted_ecdsa.pub"), CertType::User)
+      .serial(17)
+      .key_id("alice@example.com")
+      .principal("alice")
+      .validity(1_600_000_000, 1_600_003_600)
+      .critical_option("source-address", "10.0.0.0/8")
+      .critical_option("force-command", "/bin/date")
+      .extension("permit-pty", "")
+      .sign_with(&ca_key, |data| sign_ed25519(&key_pair, data))
+      .unwrap();
+
+    assert_eq!(cert.nonce.len(), NONCE_LEN);
+    assert_eq!(
+      cert
+        .critical_options
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      vec!["force-command", "source-address"]
+    );
+    assert_eq!(
+      cert
+        .extensions
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      DEFAULT_EXTENSIONS.to_vec()
+    );
+
+    let line = cert.to_openssh_line(None);
+    assert_eq!(Certificate::parse_pub(&line).unwrap(), cert);
+
+    let mut validator = CertValidator::new(CertType::User, &[ca_key]);
+    validator.clock = Clock::fixed(1_600_000_000);
+    assert!(validator.validate(&cert, "alice").is_ok());
+  }
+
+  #[test]
+  fn host_cert() {
+    let (key_pair, ca_key) = generate_ca();
+    let cert = CertificateBuilder::new(read_pub_key("unencrypted_ed25519.pub"), CertType::Host)
+      .principal("host.example.com")
+      .valid_for(Duration::from_secs(300))
+      .sign_with(&ca_key, |data| sign_ed25519(&key_pair, data))
+      .unwrap();
+
+    assert!(cert.extensions.is_empty());
+    assert_eq!(cert.valid_before - cert.valid_after, 300);
+    assert!(CertValidator::new(CertType::Host, &[ca_key])
+      .validate(&cert, "host.example.com")
+      .is_ok());
+  }
+
+  #[test]
+  fn reject_host_cert_critical_options() {
+    let (key_pair, ca_key) = generate_ca();
+    let result = CertificateBuilder::new(read_pub_key("unencrypted_ed25519.pub"), CertType::Host)
+      .principal("host.example.com")
+      .critical_option("source-address", "10.0.0.0/8")
+      .sign_with(&ca_key, |data| sign_ed25519(&key_pair, data));
+
+    match result {
+      Err(Error::InvalidCertificate(_)) => (),
+      other => panic!("Unexpected result {:?}", other),
+    }
+  }
+
+  #[test]
+  fn reject_wrong_signer() {
+    let (key_pair, _) = generate_ca();
+    let (_, other_ca_key) = generate_ca();
+    let result = CertificateBuilder::new(read_pub_key("unencrypted_ed25519.pub"), CertType::User)
+      .sign_with(&other_ca_key, |data| sign_ed25519(&key_pair, data));
+
+    assert!(result.is_err());
+  }
+
+  #[cfg(feature = "with-private")]
+  #[test]
+  fn sign_with_local_key() {
+    let secret = fs::read_to_string("fixtures/certs/ca_ed25519").unwrap();
+    let ca = crate::private::decode_secret_key(&secret, None).unwrap();
+    let cert = CertificateBuilder::new(read_pub_key("unencrypted_ed25519.pub"), CertType::User)
+      .principal("alice")
+      .sign_with_key(&ca)
+      .unwrap();
+
+    assert_eq!(cert.signature_key, read_pub_key("certs/ca_ed25519.pub"));
+    assert!(cert.signature.verify(&cert.signature_key, &cert.signed_data()).is_ok());
+
+    let truncated = crate::private::KeyPair::Ed25519 {
+      pubkey: vec![0; 32],
+      seckey: vec![0; 16],
+    };
+    let result = CertificateBuilder::new(read_pub_key("unencrypted_ed25519.pub"), CertType::User)
+      .principal("alice")
+      .sign_with_key(&truncated);
+    match result {
+      Err(Error::InvalidKeyLength) => (),
+      other => panic!("Unexpected result {:?}", other),
+    }
+  }
+}
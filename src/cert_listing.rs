@@ -0,0 +1,207 @@
+use crate::certificate::Certificate;
+use crate::encoding::Reader;
+
+const INDENT: &str = "        ";
+const ITEM_INDENT: &str = "                ";
+
+impl Certificate {
+  /// Description in the layout of `ssh-keygen -L`, without the leading file name line and with times in UTC.
+  pub fn to_listing(&self) -> String {
+    self.to_listing_with_offset(|_| 0)
+  }
+
+  /// Like `to_listing`, with each time shifted by the seconds `utc_offset` returns for it, e.g. the local UTC
+  /// offset in effect at that time.
+  pub fn to_listing_with_offset<F: Fn(u64) -> i64>(&self, utc_offset: F) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!(
+      "{}Type: {} {} certificate\n",
+      INDENT,
+      self.algorithm(),
+      self.cert_type
+    ));
+    out.push_str(&format!(
+      "{}Public key: {} {}\n",
+      INDENT,
+      self
+        .key
+        .key_type()
+        .map_or("UNKNOWN", |key_type| key_type.certificate_label()),
+      self.key.fingerprint_sha256()
+    ));
+    out.push_str(&format!(
+      "{}Signing CA: {} {} (using {})\n",
+      INDENT,
+      self.signature_key.type_label(),
+      self.signature_key.fingerprint_sha256(),
+      self.signature.hash
+    ));
+    out.push_str(&format!("{}Key ID: \"{}\"\n", INDENT, self.key_id));
+    out.push_str(&format!("{}Serial: {}\n", INDENT, self.serial));
+    out.push_str(&format!(
+      "{}Valid: {}\n",
+      INDENT,
+      self.validity_description(&utc_offset)
+    ));
+
+    out.push_str(&format!("{}Principals: ", INDENT));
+    if self.valid_principals.is_empty() {
+      out.push_str("(none)\n");
+    } else {
+      for principal in &self.valid_principals {
+        out.push_str(&format!("\n{}{}", ITEM_INDENT, principal));
+      }
+      out.push('\n');
+    }
+
+    out.push_str(&format!("{}Critical Options: ", INDENT));
+    push_options(&mut out, &self.critical_options, true);
+    out.push_str(&format!("{}Extensions: ", INDENT));
+    push_options(&mut out, &self.extensions, false);
+
+    out
+  }
+
+  fn validity_description(&self, utc_offset: &dyn Fn(u64) -> i64) -> String {
+    let format = |time: u64| format_time(i128::from(time) + i128::from(utc_offset(time)));
+
+    match (self.valid_after, self.valid_before) {
+      (0, u64::MAX) => "forever".to_string(),
+      (0, before) => format!("before {}", format(before)),
+      (after, u64::MAX) => format!("after {}", format(after)),
+      (after, before) => format!("from {} to {}", format(after), format(before)),
+    }
+  }
+}
+
+fn push_options(out: &mut String, options: &[(String, Vec<u8>)], critical: bool) {
+  if options.is_empty() {
+    out.push_str("(none)\n");
+    return;
+  }
+  out.push('\n');
+
+  for (name, data) in options {
+    out.push_str(ITEM_INDENT);
+    out.push_str(name);
+    match name.as_str() {
+      "permit-X11-forwarding"
+      | "permit-agent-forwarding"
+      | "permit-port-forwarding"
+      | "permit-pty"
+      | "permit-user-rc"
+      | "no-touch-required"
+        if !critical => {}
+      "force-command" | "source-address" if critical => {
+        let value = Reader::new(data).read_string().unwrap_or_default();
+        out.push(' ');
+        out.push_str(&String::from_utf8_lossy(value));
+      }
+      "verify-required" if critical => {}
+      _ if !data.is_empty() => {
+        let hex = data.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        out.push_str(&format!(" UNKNOWN OPTION: {} (len {})", hex, data.len()));
+      }
+      _ => out.push_str(" UNKNOWN FLAG OPTION"),
+    }
+    out.push('\n');
+  }
+}
+
+/// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SS`.
+pub(crate) fn format_time(time: i128) -> String {
+  let days = time.div_euclid(86400);
+  let seconds = time.rem_euclid(86400);
+  let (year, month, day) = civil_from_days(days);
+
+  format!(
+    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+    year,
+    month,
+    day,
+    seconds / 3600,
+    seconds / 60 % 60,
+    seconds % 60
+  )
+}
+
+// Proleptic Gregorian date of a day number, see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i128) -> (i128, i128, i128) {
+  let z = days + 719_468;
+  let era = z.div_euclid(146_097);
+  let doe = z.rem_euclid(146_097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn read_cert(name: &str) -> Certificate {
+    Certificate::parse_pub(&fs::read_to_string(format!("fixtures/certs/{}", name)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn matches_ssh_keygen() {
+    for name in ["ed25519-cert", "ecdsa521-cert", "rsa-cert"].iter() {
+      let expected = fs::read_to_string(format!("fixtures/certs/{}.listing", name)).unwrap();
+
+      assert_eq!(read_cert(&format!("{}.pub", name)).to_listing(), expected);
+    }
+  }
+
+  #[test]
+  fn validity() {
+    let mut cert = read_cert("ed25519-cert.pub");
+
+    assert_eq!(
+      cert.validity_description(&|_| 3600),
+      "from 2020-01-01T01:00:00 to 2030-01-01T01:00:00"
+    );
+    // Summer time at the start, winter time at the end
+    assert_eq!(
+      cert.validity_description(&|time| if time < 1_800_000_000 { 7200 } else { 3600 }),
+      "from 2020-01-01T02:00:00 to 2030-01-01T01:00:00"
+    );
+    cert.valid_after = 0;
+    assert_eq!(cert.validity_description(&|_| 0), "before 2030-01-01T00:00:00");
+    cert.valid_before = u64::MAX;
+    assert_eq!(cert.validity_description(&|_| 0), "forever");
+    cert.valid_after = 32_503_593_600;
+    assert_eq!(cert.validity_description(&|_| 0), "after 2999-12-31T00:00:00");
+    cert.valid_after = 0;
+    cert.valid_before = u64::MAX - 1;
+    assert_eq!(cert.validity_description(&|_| 0), "before 584554051223-11-09T07:00:14");
+  }
+
+  #[test]
+  fn unknown_options() {
+    let mut cert = read_cert("rsa-cert.pub");
+    cert.critical_options = vec![
+      ("verify-required".to_string(), vec![]),
+      ("flag@example.com".to_string(), vec![]),
+      ("value@example.com".to_string(), vec![0, 0, 0, 1, 0xab]),
+    ];
+    let listing = cert.to_listing();
+
+    assert!(listing.contains(
+      "        Critical Options: \n                verify-required\n                flag@example.com UNKNOWN FLAG OPTION\n                value@example.com UNKNOWN OPTION: 00000001ab (len 5)\n        Extensions: (none)\n"
+    ));
+  }
+
+  #[test]
+  fn times() {
+    assert_eq!(format_time(0), "1970-01-01T00:00:00");
+    assert_eq!(format_time(951_825_599), "2000-02-29T11:59:59");
+    assert_eq!(format_time(-1), "1969-12-31T23:59:59");
+  }
+}
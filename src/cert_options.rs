@@ -0,0 +1,435 @@
+use crate::cert_validator::{CertRejection, CertValidator};
+use crate::certificate::{CertType, Certificate};
+use crate::encoding::Reader;
+use crate::error::{Error, Result};
+use crate::signature::Signature;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An address block of a `source-address` critical option, e.g. `10.0.0.0/8` or a single address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+  pub address: IpAddr,
+  pub prefix_len: u8,
+}
+
+impl Cidr {
+  pub fn contains(&self, address: IpAddr) -> bool {
+    // Like sshd, IPv4 peers connected through an IPv6 socket are matched as IPv4
+    let address = match address {
+      IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+      _ => address,
+    };
+    match (self.address, address) {
+      (IpAddr::V4(network), IpAddr::V4(address)) => {
+        prefix_matches(&network.octets(), &address.octets(), self.prefix_len)
+      }
+      (IpAddr::V6(network), IpAddr::V6(address)) => {
+        prefix_matches(&network.octets(), &address.octets(), self.prefix_len)
+      }
+      _ => false,
+    }
+  }
+}
+
+/// Accepts what sshd does: an address with an optional prefix length, where all host bits have to be zero.
+impl FromStr for Cidr {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Cidr> {
+    let invalid = || Error::InvalidCertificate(format!("invalid address {}", s));
+    let (address, prefix_len) = match s.find('/') {
+      Some(idx) => (&s[..idx], Some(&s[idx + 1..])),
+      None => (s, None),
+    };
+    let address: IpAddr = address.parse().map_err(|_| invalid())?;
+    let max_len = if address.is_ipv4() { 32 } else { 128 };
+    let prefix_len = match prefix_len {
+      Some(len) if !len.is_empty() && len.bytes().all(|b| b.is_ascii_digit()) => len.parse().map_err(|_| invalid())?,
+      Some(_) => return Err(invalid()),
+      None => max_len,
+    };
+    if prefix_len > max_len {
+      return Err(invalid());
+    }
+    if host_bits_set(address, prefix_len) {
+      return Err(invalid());
+    }
+
+    Ok(Cidr { address, prefix_len })
+  }
+}
+
+impl fmt::Display for Cidr {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}/{}", self.address, self.prefix_len)
+  }
+}
+
+fn prefix_matches(network: &[u8], address: &[u8], prefix_len: u8) -> bool {
+  let full_bytes = prefix_len as usize / 8;
+  let rest_bits = prefix_len % 8;
+
+  if network[..full_bytes] != address[..full_bytes] {
+    return false;
+  }
+  if rest_bits == 0 {
+    return true;
+  }
+  let mask = 0xffu8 << (8 - rest_bits);
+  network[full_bytes] & mask == address[full_bytes] & mask
+}
+
+fn host_bits_set(address: IpAddr, prefix_len: u8) -> bool {
+  let octets = match address {
+    IpAddr::V4(v4) => v4.octets().to_vec(),
+    IpAddr::V6(v6) => v6.octets().to_vec(),
+  };
+
+  octets.iter().enumerate().any(|(i, octet)| {
+    let network_bits = (prefix_len as usize).saturating_sub(i * 8).min(8);
+    let host_mask = (0xffu16 >> network_bits) as u8;
+    octet & host_mask != 0
+  })
+}
+
+/// What a certificate allows, decoded from its critical options and extensions.
+///
+/// Extensions default to denied, so a certificate without `permit-pty` gets no terminal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CertPermissions {
+  /// `force-command`: the only command that may be run.
+  pub force_command: Option<String>,
+  /// `source-address`: the addresses the certificate may be used from, `None` for anywhere.
+  pub source_address: Option<Vec<Cidr>>,
+  /// `verify-required`: security key signatures have to prove user verification, e.g. a PIN.
+  pub verify_required: bool,
+  pub permit_pty: bool,
+  pub permit_port_forwarding: bool,
+  pub permit_agent_forwarding: bool,
+  pub permit_x11_forwarding: bool,
+  pub permit_user_rc: bool,
+  /// `no-touch-required`: security key signatures do not need to prove user presence.
+  pub no_touch_required: bool,
+}
+
+impl CertPermissions {
+  pub fn allows_source(&self, peer: IpAddr) -> bool {
+    match &self.source_address {
+      Some(cidrs) => cidrs.iter().any(|cidr| cidr.contains(peer)),
+      None => true,
+    }
+  }
+
+  pub fn check_source(&self, peer: IpAddr) -> Result<()> {
+    if self.allows_source(peer) {
+      Ok(())
+    } else {
+      Err(Error::CertificateRejected(CertRejection::SourceAddressNotAllowed(peer)))
+    }
+  }
+
+  /// Check the user presence and verification flags of a security key signature made with the certified
+  /// key. Other signatures carry no flags and always pass. To honour `no-touch-required`, verify the
+  /// signature with `VerifyOptions::require_user_presence` turned off and leave the presence check to this.
+  pub fn check_signature(&self, signature: &Signature) -> Result<()> {
+    match &signature.security_key {
+      Some(info) if !self.no_touch_required && !info.user_present() => {
+        Err(Error::CertificateRejected(CertRejection::UserPresenceRequired))
+      }
+      Some(info) if self.verify_required && !info.user_verified() => {
+        Err(Error::CertificateRejected(CertRejection::UserVerificationRequired))
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+impl Certificate {
+  /// Decode critical options and extensions. Unknown or duplicate critical options are rejected, as sshd
+  /// does, while unknown extensions are ignored. Host certificates with any critical option are rejected.
+  pub fn permissions(&self) -> Result<CertPermissions> {
+    let reject = Error::CertificateRejected;
+    let mut permissions = CertPermissions::default();
+
+    if let (CertType::Host, Some((name, _))) = (self.cert_type, self.critical_options.first()) {
+      return Err(reject(CertRejection::HostCertificateCriticalOption(name.clone())));
+    }
+    for (i, (name, data)) in self.critical_options.iter().enumerate() {
+      if self.critical_options[..i].iter().any(|(other, _)| other == name) {
+        return Err(reject(CertRejection::InvalidCriticalOption(name.clone())));
+      }
+      match name.as_str() {
+        "force-command" => {
+          let command = option_value(data).ok_or_else(|| reject(CertRejection::InvalidCriticalOption(name.clone())))?;
+          permissions.force_command = Some(command.to_string());
+        }
+        "source-address" => {
+          let invalid = || reject(CertRejection::InvalidCriticalOption(name.clone()));
+          let list = option_value(data).ok_or_else(invalid)?;
+          let cidrs = list
+            .split(',')
+            .map(|cidr| cidr.parse::<Cidr>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>>>()?;
+          permissions.source_address = Some(cidrs);
+        }
+        "verify-required" => permissions.verify_required = true,
+        _ => return Err(reject(CertRejection::UnknownCriticalOption(name.clone()))),
+      }
+    }
+
+    for (name, _) in &self.extensions {
+      match name.as_str() {
+        "permit-pty" => permissions.permit_pty = true,
+        "permit-port-forwarding" => permissions.permit_port_forwarding = true,
+        "permit-agent-forwarding" => permissions.permit_agent_forwarding = true,
+        "permit-X11-forwarding" => permissions.permit_x11_forwarding = true,
+        "permit-user-rc" => permissions.permit_user_rc = true,
+        "no-touch-required" => permissions.no_touch_required = true,
+        _ => (),
+      }
+    }
+
+    Ok(permissions)
+  }
+
+  /// Raw data of a critical option.
+  pub fn critical_option(&self, name: &str) -> Option<&[u8]> {
+    find_option(&self.critical_options, name)
+  }
+
+  /// Raw data of an extension.
+  pub fn extension(&self, name: &str) -> Option<&[u8]> {
+    find_option(&self.extensions, name)
+  }
+}
+
+impl CertValidator {
+  /// Validate `cert` for `principal` connecting from `peer` and decode what it permits.
+  pub fn authorize(&self, cert: &Certificate, principal: &str, peer: IpAddr) -> Result<CertPermissions> {
+    let permissions = self.validate(cert, principal)?;
+    permissions.check_source(peer)?;
+
+    Ok(permissions)
+  }
+}
+
+fn find_option<'a>(options: &'a [(String, Vec<u8>)], name: &str) -> Option<&'a [u8]> {
+  options
+    .iter()
+    .find(|(other, _)| other == name)
+    .map(|(_, data)| data.as_slice())
+}
+
+/// Options with a value carry it as a single SSH string.
+fn option_value(data: &[u8]) -> Option<&str> {
+  let mut reader = Reader::new(data);
+  let value = reader.read_string().ok()?;
+
+  if reader.remaining_len() > 0 {
+    return None;
+  }
+  std::str::from_utf8(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cert_builder::CertificateBuilder;
+  use crate::cert_validator::Clock;
+  use crate::public::PublicKey;
+  use crate::signature::{SecurityKeyInfo, SignatureHash};
+  use ring::rand::SystemRandom;
+  use ring::signature::{Ed25519KeyPair, KeyPair};
+  use std::fs;
+
+  fn read_cert(name: &str) -> Certificate {
+    Certificate::parse_pub(&fs::read_to_string(format!("fixtures/certs/{}", name)).unwrap()).unwrap()
+  }
+
+  fn rejection<T: fmt::Debug>(result: Result<T>) -> CertRejection {
+    match result {
+      Err(Error::CertificateRejected(rejection)) => rejection,
+      other => panic!("Unexpected result {:?}", other),
+    }
+  }
+
+  #[test]
+  fn parse_cidr() {
+    let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+    assert!(cidr.contains("::ffff:10.1.2.3".parse().unwrap()));
+    assert!(!cidr.contains("11.0.0.0".parse().unwrap()));
+
+    let cidr: Cidr = "2001:db8::/33".parse().unwrap();
+    assert!(cidr.contains("2001:db8:7fff::1".parse().unwrap()));
+    assert!(!cidr.contains("2001:db8:8000::1".parse().unwrap()));
+    assert!(!cidr.contains("10.0.0.1".parse().unwrap()));
+
+    let cidr: Cidr = "192.168.1.1".parse().unwrap();
+    assert_eq!(format!("{}", cidr), "192.168.1.1/32");
+    assert!(cidr.contains("192.168.1.1".parse().unwrap()));
+    assert!(!cidr.contains("192.168.1.2".parse().unwrap()));
+
+    assert!("0.0.0.0/0"
+      .parse::<Cidr>()
+      .unwrap()
+      .contains("8.8.8.8".parse().unwrap()));
+    for invalid in [
+      "10.0.0.1/8",
+      "10.0.0.0/33",
+      "10.0.0.0/",
+      "10.0.0.0/+8",
+      "example.com",
+      "",
+    ]
+    .iter()
+    {
+      assert!(invalid.parse::<Cidr>().is_err(), "{}", invalid);
+    }
+  }
+
+  #[test]
+  fn fixture_permissions() {
+    let cert = read_cert("ed25519-cert.pub");
+    let permissions = cert.permissions().unwrap();
+
+    assert_eq!(permissions.force_command.as_deref(), Some("/usr/bin/true"));
+    assert_eq!(
+      permissions.source_address,
+      Some(vec!["10.0.0.0/8".parse().unwrap(), "192.168.1.1".parse().unwrap()])
+    );
+    assert!(!permissions.verify_required);
+    assert!(permissions.permit_pty);
+    assert!(permissions.permit_port_forwarding);
+    assert!(permissions.permit_agent_forwarding);
+    assert!(permissions.permit_x11_forwarding);
+    assert!(permissions.permit_user_rc);
+    assert!(!permissions.no_touch_required);
+    assert!(permissions.allows_source("10.20.30.40".parse().unwrap()));
+    assert!(permissions.allows_source("192.168.1.1".parse().unwrap()));
+    assert!(!permissions.allows_source("192.168.1.2".parse().unwrap()));
+    assert_eq!(cert.extension("permit-pty"), Some(&[][..]));
+    assert_eq!(cert.extension("no-touch-required"), None);
+
+    let permissions = read_cert("rsa-cert.pub").permissions().unwrap();
+    assert_eq!(permissions, CertPermissions::default());
+    assert!(permissions.allows_source("8.8.8.8".parse().unwrap()));
+  }
+
+  #[test]
+  fn unknown_critical_option_fails_closed() {
+    let mut cert = read_cert("ecdsa-cert.pub");
+    cert
+      .critical_options
+      .push(("no-such-option@example.com".to_string(), vec![]));
+
+    assert_eq!(
+      rejection(cert.permissions()),
+      CertRejection::UnknownCriticalOption("no-such-option@example.com".to_string())
+    );
+
+    // Unknown extensions on the other hand are ignored
+    let mut cert = read_cert("ecdsa-cert.pub");
+    cert
+      .extensions
+      .push(("no-such-extension@example.com".to_string(), vec![]));
+    assert!(cert.permissions().is_ok());
+  }
+
+  #[test]
+  fn validate_rejects_unknown_critical_option() {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let ca_key = PublicKey::Ed25519(key_pair.public_key().as_ref().to_vec());
+    let cert = CertificateBuilder::new(read_cert("ecdsa-cert.pub").key, CertType::User)
+      .principal("alice")
+      .critical_option("no-such-option@example.com", "")
+      .sign_with(&ca_key, |data| {
+        Ok(Signature {
+          hash: SignatureHash::Ed25519,
+          signature: key_pair.sign(data).as_ref().to_vec(),
+          security_key: None,
+        })
+      })
+      .unwrap();
+
+    assert_eq!(
+      rejection(CertValidator::new(CertType::User, &[ca_key]).validate(&cert, "alice")),
+      CertRejection::UnknownCriticalOption("no-such-option@example.com".to_string())
+    );
+  }
+
+  #[test]
+  fn host_cert_with_critical_option() {
+    let mut cert = read_cert("rsa-cert.pub");
+    cert
+      .critical_options
+      .push(("force-command".to_string(), b"\0\0\0\x0d/usr/bin/true".to_vec()));
+
+    assert_eq!(
+      rejection(cert.permissions()),
+      CertRejection::HostCertificateCriticalOption("force-command".to_string())
+    );
+  }
+
+  #[test]
+  fn malformed_critical_options() {
+    let mut cert = read_cert("ed25519-cert.pub");
+    cert.critical_options[0].1 = b"/usr/bin/true".to_vec();
+    assert_eq!(
+      rejection(cert.permissions()),
+      CertRejection::InvalidCriticalOption("force-command".to_string())
+    );
+
+    let mut cert = read_cert("ed25519-cert.pub");
+    let duplicate = cert.critical_options[1].clone();
+    cert.critical_options.push(duplicate);
+    assert_eq!(
+      rejection(cert.permissions()),
+      CertRejection::InvalidCriticalOption("source-address".to_string())
+    );
+  }
+
+  #[test]
+  fn security_key_flags() {
+    let signature = |flags| Signature {
+      hash: SignatureHash::SkEd25519,
+      signature: vec![],
+      security_key: Some(SecurityKeyInfo { flags, counter: 1 }),
+    };
+    let mut permissions = CertPermissions::default();
+
+    assert!(permissions.check_signature(&signature(0x01)).is_ok());
+    assert_eq!(
+      rejection(permissions.check_signature(&signature(0x00))),
+      CertRejection::UserPresenceRequired
+    );
+    permissions.no_touch_required = true;
+    assert!(permissions.check_signature(&signature(0x00)).is_ok());
+    permissions.verify_required = true;
+    assert_eq!(
+      rejection(permissions.check_signature(&signature(0x01))),
+      CertRejection::UserVerificationRequired
+    );
+    assert!(permissions.check_signature(&signature(0x04)).is_ok());
+  }
+
+  #[test]
+  fn authorize() {
+    let ca_key = PublicKey::parse_pub(&fs::read_to_string("fixtures/certs/ca_ed25519.pub").unwrap()).unwrap();
+    let mut validator = CertValidator::new(CertType::User, &[ca_key]);
+    validator.clock = Clock::fixed(1_735_689_600);
+    let cert = read_cert("ed25519-cert.pub");
+
+    let permissions = validator
+      .authorize(&cert, "alice", "10.0.0.1".parse().unwrap())
+      .unwrap();
+    assert_eq!(permissions.force_command.as_deref(), Some("/usr/bin/true"));
+    assert_eq!(
+      rejection(validator.authorize(&cert, "alice", "172.16.0.1".parse().unwrap())),
+      CertRejection::SourceAddressNotAllowed("172.16.0.1".parse().unwrap())
+    );
+  }
+}
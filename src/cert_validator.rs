@@ -0,0 +1,302 @@
+use crate::cert_options::CertPermissions;
+use crate::certificate::{CertType, Certificate};
+use crate::error::{Error, Result};
+use crate::public::PublicKey;
+use crate::signature::VerifyOptions;
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Why a certificate was not accepted by a `CertValidator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertRejection {
+  /// The signing CA is not trusted, with the SHA-256 fingerprint of its key.
+  UntrustedCa(String),
+  /// The CA signature could not be verified, with the reason.
+  InvalidSignature(String),
+  WrongType {
+    expected: CertType,
+    found: CertType,
+  },
+  NotYetValid {
+    valid_after: u64,
+    now: u64,
+  },
+  Expired {
+    valid_before: u64,
+    now: u64,
+  },
+  /// The certificate lists no principals at all.
+  NoPrincipals,
+  PrincipalNotAllowed(String),
+  UnknownCriticalOption(String),
+  /// A critical option is malformed or given more than once.
+  InvalidCriticalOption(String),
+  /// Host certificates may not have critical options at all, OpenSSH refuses them.
+  HostCertificateCriticalOption(String),
+  SourceAddressNotAllowed(IpAddr),
+  /// A security key signature lacks the user presence flag.
+  UserPresenceRequired,
+  /// A security key signature lacks the user verification flag required by `verify-required`.
+  UserVerificationRequired,
+}
+
+impl fmt::Display for CertRejection {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CertRejection::UntrustedCa(fingerprint) => write!(f, "untrusted certificate authority {}", fingerprint),
+      CertRejection::InvalidSignature(reason) => write!(f, "invalid ca signature: {}", reason),
+      CertRejection::WrongType { expected, found } => {
+        write!(f, "expected a {} certificate, got a {} certificate", expected, found)
+      }
+      CertRejection::NotYetValid { valid_after, now } => write!(f, "not valid before {} (now {})", valid_after, now),
+      CertRejection::Expired { valid_before, now } => write!(f, "expired at {} (now {})", valid_before, now),
+      CertRejection::NoPrincipals => write!(f, "certificate lacks principal list"),
+      CertRejection::PrincipalNotAllowed(principal) => write!(f, "principal {} not allowed", principal),
+      CertRejection::UnknownCriticalOption(name) => write!(f, "unsupported critical option {}", name),
+      CertRejection::InvalidCriticalOption(name) => write!(f, "invalid critical option {}", name),
+      CertRejection::HostCertificateCriticalOption(name) => {
+        write!(f, "critical option {} in host certificate", name)
+      }
+      CertRejection::SourceAddressNotAllowed(address) => write!(f, "source address {} not allowed", address),
+      CertRejection::UserPresenceRequired => write!(f, "user presence required"),
+      CertRejection::UserVerificationRequired => write!(f, "user verification required"),
+    }
+  }
+}
+
+/// Source of the current time in seconds since the epoch, the system clock by default.
+///
+/// Cloned clocks share the underlying function, so one controllable clock can drive several validators.
+#[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> u64 + Send + Sync>);
+
+impl Clock {
+  pub fn new<F: Fn() -> u64 + Send + Sync + 'static>(now: F) -> Clock {
+    Clock(Arc::new(now))
+  }
+
+  /// A clock standing still at `time`.
+  pub fn fixed(time: u64) -> Clock {
+    Clock::new(move || time)
+  }
+
+  pub fn now(&self) -> u64 {
+    (self.0)()
+  }
+}
+
+impl Default for Clock {
+  fn default() -> Clock {
+    Clock::new(system_time)
+  }
+}
+
+impl fmt::Debug for Clock {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("Clock(..)")
+  }
+}
+
+/// Checks certificates the way `sshd` does before accepting them: signed by a trusted CA, of the expected
+/// type, currently valid and issued for the requested principal.
+#[derive(Debug, Clone)]
+pub struct CertValidator {
+  pub cert_type: CertType,
+  /// Keys of the trusted certificate authorities.
+  pub trusted_cas: HashSet<PublicKey>,
+  /// Policy for the CA signature.
+  pub verify_options: VerifyOptions,
+  /// Current time, replaceable for tests.
+  pub clock: Clock,
+}
+
+impl CertValidator {
+  pub fn new(cert_type: CertType, trusted_cas: &[PublicKey]) -> CertValidator {
+    CertValidator {
+      cert_type,
+      trusted_cas: trusted_cas.iter().cloned().collect(),
+      verify_options: VerifyOptions::default(),
+      clock: Clock::default(),
+    }
+  }
+
+  /// Check `cert` for `principal` and decode what it permits, failing with `Error::CertificateRejected` and
+  /// the first problem found. Like sshd, certificates with unknown critical options are rejected. The caller
+  /// still has to enforce the returned permissions, `authorize` also checks the peer address.
+  ///
+  /// Certificates without principals are always rejected, OpenSSH only does so for user certificates.
+  pub fn validate(&self, cert: &Certificate, principal: &str) -> Result<CertPermissions> {
+    self.check(cert, principal).map_err(Error::CertificateRejected)?;
+    cert.permissions()
+  }
+
+  fn check(&self, cert: &Certificate, principal: &str) -> std::result::Result<(), CertRejection> {
+    if !self.trusted_cas.contains(&cert.signature_key) {
+      return Err(CertRejection::UntrustedCa(cert.signature_key.fingerprint_sha256()));
+    }
+    cert
+      .signature
+      .verify_with(&cert.signature_key, &cert.signed_data(), &self.verify_options)
+      .map_err(|err| CertRejection::InvalidSignature(format!("{}", err)))?;
+
+    if cert.cert_type != self.cert_type {
+      return Err(CertRejection::WrongType {
+        expected: self.cert_type,
+        found: cert.cert_type,
+      });
+    }
+
+    let now = self.clock.now();
+    if now < cert.valid_after {
+      return Err(CertRejection::NotYetValid {
+        valid_after: cert.valid_after,
+        now,
+      });
+    }
+    if now >= cert.valid_before {
+      return Err(CertRejection::Expired {
+        valid_before: cert.valid_before,
+        now,
+      });
+    }
+
+    if cert.valid_principals.is_empty() {
+      return Err(CertRejection::NoPrincipals);
+    }
+    if !cert.valid_principals.iter().any(|valid| valid == principal) {
+      return Err(CertRejection::PrincipalNotAllowed(principal.to_string()));
+    }
+
+    Ok(())
+  }
+}
+
+pub(crate) fn system_time() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+  use std::sync::atomic::{AtomicU64, Ordering};
+
+  fn read_cert(name: &str) -> Certificate {
+    Certificate::parse_pub(&fs::read_to_string(format!("fixtures/certs/{}", name)).unwrap()).unwrap()
+  }
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  fn rejection<T: fmt::Debug>(result: Result<T>) -> CertRejection {
+    match result {
+      Err(Error::CertificateRejected(rejection)) => rejection,
+      other => panic!("Unexpected result {:?}", other),
+    }
+  }
+
+  fn user_validator() -> CertValidator {
+    let mut validator = CertValidator::new(CertType::User, &[read_pub_key("certs/ca_ed25519.pub")]);
+    // 2025-01-01
+    validator.clock = Clock::fixed(1_735_689_600);
+    validator
+  }
+
+  #[test]
+  fn accept_user_cert() {
+    let validator = user_validator();
+    let cert = read_cert("ed25519-cert.pub");
+
+    assert!(validator.validate(&cert, "alice").is_ok());
+    assert!(validator.validate(&cert, "bob").is_ok());
+    assert_eq!(
+      rejection(validator.validate(&cert, "mallory")),
+      CertRejection::PrincipalNotAllowed("mallory".to_string())
+    );
+  }
+
+  #[test]
+  fn accept_host_cert() {
+    let mut validator = CertValidator::new(CertType::Host, &[read_pub_key("certs/ca_rsa.pub")]);
+    // The host certificate never expires
+    validator.clock = Clock::fixed(u64::MAX - 1);
+
+    assert!(validator.validate(&read_cert("rsa-cert.pub"), "10.0.0.1").is_ok());
+  }
+
+  #[test]
+  fn reject_untrusted_ca() {
+    let validator = CertValidator::new(CertType::User, &[read_pub_key("certs/ca_rsa.pub")]);
+
+    assert_eq!(
+      rejection(validator.validate(&read_cert("ed25519-cert.pub"), "alice")),
+      CertRejection::UntrustedCa("SHA256:MTG9kmmXELTt+6dN5Qv1oCo4F4y4KEtd3lJoOiRALV0".to_string())
+    );
+  }
+
+  #[test]
+  fn reject_tampered_cert() {
+    let mut cert = read_cert("ed25519-cert.pub");
+    cert.valid_principals.push("mallory".to_string());
+
+    match rejection(user_validator().validate(&cert, "mallory")) {
+      CertRejection::InvalidSignature(_) => (),
+      other => panic!("Unexpected rejection {:?}", other),
+    }
+  }
+
+  #[test]
+  fn reject_wrong_type() {
+    let mut validator = user_validator();
+    validator.cert_type = CertType::Host;
+
+    assert_eq!(
+      rejection(validator.validate(&read_cert("ed25519-cert.pub"), "alice")),
+      CertRejection::WrongType {
+        expected: CertType::Host,
+        found: CertType::User,
+      }
+    );
+  }
+
+  #[test]
+  fn clock_debug_is_opaque() {
+    let clock = Clock::new(|| panic!("clock called"));
+    assert_eq!(format!("{:?}", clock), "Clock(..)");
+  }
+
+  #[test]
+  fn reject_outside_validity() {
+    let cert = read_cert("ed25519-cert.pub");
+    let now = Arc::new(AtomicU64::new(1_577_836_799));
+    let mut validator = user_validator();
+    validator.clock = {
+      let now = now.clone();
+      Clock::new(move || now.load(Ordering::SeqCst))
+    };
+
+    assert_eq!(
+      rejection(validator.validate(&cert, "alice")),
+      CertRejection::NotYetValid {
+        valid_after: 1_577_836_800,
+        now: 1_577_836_799,
+      }
+    );
+    now.store(1_577_836_800, Ordering::SeqCst);
+    assert!(validator.validate(&cert, "alice").is_ok());
+    now.store(1_893_456_000, Ordering::SeqCst);
+    assert_eq!(
+      rejection(validator.validate(&cert, "alice")),
+      CertRejection::Expired {
+        valid_before: 1_893_456_000,
+        now: 1_893_456_000,
+      }
+    );
+  }
+}
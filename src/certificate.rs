@@ -0,0 +1,362 @@
+use crate::encoding::{Reader, Writer};
+use crate::error::{Error, Result};
+use crate::key_type::KeyType;
+use crate::public::PublicKey;
+use crate::signature::Signature;
+use std::fmt;
+
+/// Whether a certificate authenticates a user or a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CertType {
+  User,
+  Host,
+}
+
+impl CertType {
+  pub fn from_u32(value: u32) -> Result<CertType> {
+    match value {
+      1 => Ok(CertType::User),
+      2 => Ok(CertType::Host),
+      _ => Err(Error::InvalidCertificate(format!("unknown certificate type {}", value))),
+    }
+  }
+
+  pub fn to_u32(self) -> u32 {
+    match self {
+      CertType::User => 1,
+      CertType::Host => 2,
+    }
+  }
+}
+
+impl fmt::Display for CertType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      CertType::User => write!(f, "user"),
+      CertType::Host => write!(f, "host"),
+    }
+  }
+}
+
+/// An OpenSSH certificate (`*-cert-v01@openssh.com`), see PROTOCOL.certkeys in the OpenSSH sources.
+///
+/// Times are seconds since the epoch, `valid_before` is `u64::MAX` for certificates that never expire.
+/// Critical options and extensions keep their raw data, which for options with a value is itself an SSH
+/// string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Certificate {
+  pub nonce: Vec<u8>,
+  /// The certified key.
+  pub key: PublicKey,
+  pub serial: u64,
+  pub cert_type: CertType,
+  pub key_id: String,
+  /// An empty list means the certificate is valid for any principal.
+  pub valid_principals: Vec<String>,
+  pub valid_after: u64,
+  pub valid_before: u64,
+  pub critical_options: Vec<(String, Vec<u8>)>,
+  pub extensions: Vec<(String, Vec<u8>)>,
+  pub reserved: Vec<u8>,
+  /// The key of the certificate authority.
+  pub signature_key: PublicKey,
+  pub signature: Signature,
+}
+
+impl Certificate {
+  pub(crate) fn is_certificate_algorithm(name: &[u8]) -> bool {
+    certified_key_type(name).is_some()
+  }
+
+  pub fn parse_raw(raw_cert: &[u8]) -> Result<Certificate> {
+    let mut reader = Reader::new(raw_cert);
+
+    let algo = reader.read_string()?;
+    let key_type = certified_key_type(algo).ok_or(Error::CouldNotReadKey)?;
+    let nonce = Vec::from(reader.read_string()?);
+    let key = PublicKey::read_key_body(key_type.name().as_bytes(), &mut reader)?;
+    let serial = reader.read_u64()?;
+    let cert_type = CertType::from_u32(reader.read_u32()?)?;
+    let key_id = std::str::from_utf8(reader.read_string()?)?.to_string();
+
+    let mut principals = Reader::new(reader.read_string()?);
+    let mut valid_principals = Vec::new();
+    while principals.remaining_len() > 0 {
+      valid_principals.push(std::str::from_utf8(principals.read_string()?)?.to_string());
+    }
+
+    let valid_after = reader.read_u64()?;
+    let valid_before = reader.read_u64()?;
+    let critical_options = read_options(reader.read_string()?)?;
+    let extensions = read_options(reader.read_string()?)?;
+    let reserved = Vec::from(reader.read_string()?);
+
+    let signature_key = PublicKey::parse_raw(reader.read_string()?)?;
+    if let PublicKey::Certificate(_) = signature_key {
+      return Err(Error::InvalidCertificate("signature key is a certificate".to_string()));
+    }
+    let (signature, rest) = Signature::parse_raw(reader.read_string()?)?;
+    if !rest.is_empty() || reader.remaining_len() > 0 {
+      return Err(Error::InvalidCertificate("trailing data".to_string()));
+    }
+
+    Ok(Certificate {
+      nonce,
+      key,
+      serial,
+      cert_type,
+      key_id,
+      valid_principals,
+      valid_after,
+      valid_before,
+      critical_options,
+      extensions,
+      reserved,
+      signature_key,
+      signature,
+    })
+  }
+
+  /// Parse a single line of a `-cert.pub` file.
+  pub fn parse_pub(line: &str) -> Result<Certificate> {
+    match PublicKey::parse_pub(line)? {
+      PublicKey::Certificate(cert) => Ok(*cert),
+      _ => Err(Error::InvalidCertificate("not a certificate".to_string())),
+    }
+  }
+
+  pub fn to_openssh_line(&self, comment: Option<&str>) -> String {
+    let encoded = base64::encode_config(self.to_ssh_cert(), base64::STANDARD);
+
+    match comment {
+      Some(comment) => format!("{} {} {}", self.algorithm(), encoded, comment),
+      None => format!("{} {}", self.algorithm(), encoded),
+    }
+  }
+
+  /// Wire name, e.g. `ssh-ed25519-cert-v01@openssh.com`.
+  pub fn algorithm(&self) -> &'static str {
+    self.key.key_type().map_or("", |key_type| key_type.certificate_name())
+  }
+
+  pub fn to_ssh_cert(&self) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_string(self.algorithm().as_bytes());
+    self.write_body(&mut writer);
+    writer.buffer
+  }
+
+  /// The part of the wire blob covered by the CA signature, i.e. everything but the signature itself.
+  pub fn signed_data(&self) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_string(self.algorithm().as_bytes());
+    self.write_signed_body(&mut writer);
+    writer.buffer
+  }
+
+  pub(crate) fn write_body(&self, writer: &mut Writer) {
+    self.write_signed_body(writer);
+    writer.write_string(&self.signature.to_ssh_sig());
+  }
+
+  fn write_signed_body(&self, writer: &mut Writer) {
+    writer.write_string(&self.nonce);
+    self.key.write_key_body(writer);
+    writer.write_u64(self.serial);
+    writer.write_u32(self.cert_type.to_u32());
+    writer.write_string(self.key_id.as_bytes());
+
+    let mut principals = Writer::new();
+    for principal in &self.valid_principals {
+      principals.write_string(principal.as_bytes());
+    }
+    writer.write_string(&principals.buffer);
+
+    writer.write_u64(self.valid_after);
+    writer.write_u64(self.valid_before);
+    writer.write_string(&write_options(&self.critical_options));
+    writer.write_string(&write_options(&self.extensions));
+    writer.write_string(&self.reserved);
+    writer.write_string(&self.signature_key.to_ssh_key());
+  }
+}
+
+fn certified_key_type(name: &[u8]) -> Option<KeyType> {
+  KeyType::all()
+    .iter()
+    .find(|key_type| key_type.certificate_name().as_bytes() == name)
+    .copied()
+}
+
+fn read_options(raw: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+  let mut reader = Reader::new(raw);
+  let mut options = Vec::new();
+
+  while reader.remaining_len() > 0 {
+    let name = std::str::from_utf8(reader.read_string()?)?.to_string();
+    let data = Vec::from(reader.read_string()?);
+    options.push((name, data));
+  }
+
+  Ok(options)
+}
+
+fn write_options(options: &[(String, Vec<u8>)]) -> Vec<u8> {
+  let mut writer = Writer::new();
+
+  for (name, data) in options {
+    writer.write_string(name.as_bytes());
+    writer.write_string(data);
+  }
+
+  writer.buffer
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::signature::SignatureHash;
+  use std::fs;
+
+  fn read_cert(name: &str) -> Certificate {
+    Certificate::parse_pub(&fs::read_to_string(format!("fixtures/certs/{}", name)).unwrap()).unwrap()
+  }
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  fn string_data(value: &str) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.write_string(value.as_bytes());
+    writer.buffer
+  }
+
+  #[test]
+  fn read_user_cert() {
+    let cert = read_cert("ed25519-cert.pub");
+
+    assert_eq!(cert.algorithm(), "ssh-ed25519-cert-v01@openssh.com");
+    assert_eq!(cert.key, read_pub_key("unencrypted_ed25519.pub"));
+    assert_eq!(cert.nonce.len(), 32);
+    assert_eq!(cert.serial, 42);
+    assert_eq!(cert.cert_type, CertType::User);
+    assert_eq!(cert.key_id, "test-user");
+    assert_eq!(cert.valid_principals, vec!["alice", "bob"]);
+    // 2020-01-01 and 2030-01-01
+    assert_eq!(cert.valid_after, 1_577_836_800);
+    assert_eq!(cert.valid_before, 1_893_456_000);
+    assert_eq!(
+      cert.critical_options,
+      vec![
+        ("force-command".to_string(), string_data("/usr/bin/true")),
+        ("source-address".to_string(), string_data("10.0.0.0/8,192.168.1.1")),
+      ]
+    );
+    assert_eq!(
+      cert
+        .extensions
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>(),
+      vec![
+        "permit-X11-forwarding",
+        "permit-agent-forwarding",
+        "permit-port-forwarding",
+        "permit-pty",
+        "permit-user-rc",
+      ]
+    );
+    assert!(cert.extensions.iter().all(|(_, data)| data.is_empty()));
+    assert_eq!(cert.signature_key, read_pub_key("certs/ca_ed25519.pub"));
+    assert_eq!(
+      cert.signature_key.fingerprint_sha256(),
+      "SHA256:MTG9kmmXELTt+6dN5Qv1oCo4F4y4KEtd3lJoOiRALV0"
+    );
+    assert_eq!(cert.signature.hash, SignatureHash::Ed25519);
+    assert!(cert.signature.verify(&cert.signature_key, &cert.signed_data()).is_ok());
+  }
+
+  #[test]
+  fn read_host_cert() {
+    let cert = read_cert("rsa-cert.pub");
+
+    assert_eq!(cert.key, read_pub_key("unencrypted_rsa.pub"));
+    assert_eq!(cert.serial, 7);
+    assert_eq!(cert.cert_type, CertType::Host);
+    assert_eq!(cert.key_id, "test-host");
+    assert_eq!(cert.valid_principals, vec!["host.example.com", "10.0.0.1"]);
+    assert_eq!(cert.valid_before, u64::MAX);
+    assert!(cert.critical_options.is_empty());
+    assert!(cert.extensions.is_empty());
+    assert_eq!(cert.signature.hash, SignatureHash::RsaSha512);
+    assert_eq!(
+      cert.signature_key.fingerprint_sha256(),
+      "SHA256:3551mCMYrGMLlV8uAun31F2KLByxzwhPFE+yPuEtJgA"
+    );
+    assert!(cert.signature.verify(&cert.signature_key, &cert.signed_data()).is_ok());
+  }
+
+  #[test]
+  fn read_ecdsa_certs() {
+    for (name, key_name, serial) in [
+      ("ecdsa-cert.pub", "unencrypted_ecdsa.pub", 1),
+      ("ecdsa384-cert.pub", "unencrypted_ecdsa384.pub", 2),
+      ("ecdsa521-cert.pub", "unencrypted_ecdsa521.pub", 3),
+    ]
+    .iter()
+    {
+      let cert = read_cert(name);
+
+      assert_eq!(cert.key, read_pub_key(key_name));
+      assert_eq!(cert.serial, *serial);
+      assert_eq!(cert.valid_principals, vec!["alice"]);
+      assert!(cert.signature.verify(&cert.signature_key, &cert.signed_data()).is_ok());
+    }
+  }
+
+  #[test]
+  fn openssh_line_roundtrip() {
+    for name in [
+      "ed25519-cert.pub",
+      "ecdsa-cert.pub",
+      "ecdsa384-cert.pub",
+      "ecdsa521-cert.pub",
+      "rsa-cert.pub",
+    ]
+    .iter()
+    {
+      let line = fs::read_to_string(format!("fixtures/certs/{}", name)).unwrap();
+      let line = line.trim();
+      let key = PublicKey::parse_pub(line).unwrap();
+      let comment = line.splitn(3, ' ').nth(2);
+
+      assert_eq!(key.to_openssh_line(comment), line);
+      assert_eq!(read_cert(name).to_openssh_line(comment), line);
+    }
+  }
+
+  #[test]
+  fn certificate_key() {
+    let key = read_pub_key("certs/ed25519-cert.pub");
+    let plain = read_pub_key("unencrypted_ed25519.pub");
+
+    assert_eq!(key.algorithm(), "ssh-ed25519-cert-v01@openssh.com");
+    assert_eq!(key.key_type(), Some(KeyType::Ed25519));
+    assert_eq!(key.type_label(), "ED25519-CERT");
+    assert_eq!(key.bits(), 256);
+    // Like OpenSSH, fingerprints are those of the certified key
+    assert_eq!(key.fingerprint_sha256(), plain.fingerprint_sha256());
+    assert_ne!(key, plain);
+    assert!(key.to_pkix_der().is_err());
+    assert!(Certificate::parse_pub(&fs::read_to_string("fixtures/unencrypted_ed25519.pub").unwrap()).is_err());
+  }
+
+  #[test]
+  fn reject_trailing_data() {
+    let mut blob = read_cert("ed25519-cert.pub").to_ssh_cert();
+    blob.push(0);
+
+    assert!(Certificate::parse_raw(&blob).is_err());
+  }
+}
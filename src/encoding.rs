@@ -40,6 +40,16 @@ impl<'a> Reader<'a> {
     }
   }
 
+  pub fn read_u64(&mut self) -> Result<u64> {
+    if self.remaining_len() >= 8 {
+      let u = BigEndian::read_u64(&self.data[self.position..]);
+      self.position += 8;
+      Ok(u)
+    } else {
+      Err(Error::BufferTooShort)
+    }
+  }
+
   pub fn read_string(&mut self) -> Result<&'a [u8]> {
     let len = self.read_u32()? as usize;
     if self.remaining_len() >= len {
@@ -72,6 +82,12 @@ impl Writer {
     self.buffer.extend_from_slice(&bytes);
   }
 
+  pub fn write_u64(&mut self, i: u64) {
+    let mut bytes = [0u8; 8];
+    BigEndian::write_u64(&mut bytes, i);
+    self.buffer.extend_from_slice(&bytes);
+  }
+
   pub fn write_string(&mut self, s: &[u8]) {
     let mut len_bytes = [0u8; 4];
     BigEndian::write_u32(&mut len_bytes, s.len() as u32);
@@ -1,3 +1,5 @@
+use crate::cert_authority::IssuanceRejection;
+use crate::cert_validator::CertRejection;
 use std::fmt;
 
 #[derive(Debug)]
@@ -9,6 +11,20 @@ pub enum Error {
   KeyIsEncrypted,
   BufferTooShort,
   RequestFailure,
+  InvalidLine(usize, String),
+  InvalidJwk(String),
+  UnsupportedKeyType(String),
+  KeyTooSmall(u32, u32),
+  KeyTooLarge(u32, u32),
+  InvalidRsaModulus,
+  InvalidRsaExponent,
+  InvalidEcPoint,
+  CompressedEcPoint,
+  AlgorithmNotAllowed(String),
+  UserPresenceRequired,
+  InvalidCertificate(String),
+  CertificateRejected(CertRejection),
+  IssuanceRejected(IssuanceRejection),
   Base64(String),
   IO(String),
   OpenSsl(String),
@@ -26,6 +42,20 @@ impl fmt::Display for Error {
       Error::KeyIsEncrypted => write!(f, "key is encrypted"),
       Error::BufferTooShort => write!(f, "buffer too short"),
       Error::RequestFailure => write!(f, "request failure"),
+      Error::InvalidLine(line, msg) => write!(f, "invalid entry in line {}: {}", line, msg),
+      Error::InvalidJwk(msg) => write!(f, "invalid jwk: {}", msg),
+      Error::UnsupportedKeyType(name) => write!(f, "unsupported key type: {}", name),
+      Error::KeyTooSmall(bits, min) => write!(f, "key too small: {} bits, at least {} required", bits, min),
+      Error::KeyTooLarge(bits, max) => write!(f, "key too large: {} bits, at most {} supported", bits, max),
+      Error::InvalidRsaModulus => write!(f, "invalid rsa modulus"),
+      Error::InvalidRsaExponent => write!(f, "invalid rsa exponent"),
+      Error::InvalidEcPoint => write!(f, "ec point is not on the curve"),
+      Error::CompressedEcPoint => write!(f, "compressed ec points are not supported"),
+      Error::AlgorithmNotAllowed(name) => write!(f, "signature algorithm not allowed: {}", name),
+      Error::UserPresenceRequired => write!(f, "security key signature without user presence"),
+      Error::InvalidCertificate(msg) => write!(f, "invalid certificate: {}", msg),
+      Error::CertificateRejected(reason) => write!(f, "certificate rejected: {}", reason),
+      Error::IssuanceRejected(reason) => write!(f, "certificate request rejected: {}", reason),
       Error::Base64(msg) => write!(f, "invalid base64: {}", msg),
       Error::IO(msg) => write!(f, "I/O error: {}", msg),
       Error::OpenSsl(msg) => write!(f, "I/O error: {}", msg),
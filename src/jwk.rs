@@ -0,0 +1,301 @@
+use crate::error::{Error, Result};
+use crate::public::PublicKey;
+use ring::digest;
+use ring::signature::ED25519_PUBLIC_KEY_LEN;
+use serde::{Deserialize, Serialize};
+
+/// JSON Web Key (RFC 7517) representation of a public key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+  pub kty: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub crv: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub n: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub e: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub x: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub y: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub kid: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub alg: Option<String>,
+}
+
+impl Jwk {
+  pub fn parse_json(json: &str) -> Result<Jwk> {
+    serde_json::from_str(json).map_err(|e| Error::InvalidJwk(format!("{}", e)))
+  }
+
+  pub fn to_json(&self) -> String {
+    // Serializing a struct of strings can not fail
+    serde_json::to_string(self).unwrap()
+  }
+
+  /// RFC 7638 thumbprint, i.e. the base64url encoded SHA-256 of the required members.
+  pub fn thumbprint(&self) -> Result<String> {
+    // Quoted and escaped as a JSON string, imported members are not necessarily base64url
+    let member = |name: &str, value: &Option<String>| match value {
+      Some(value) => serde_json::to_string(value).map_err(|e| Error::InvalidJwk(format!("{}", e))),
+      None => Err(Error::InvalidJwk(format!("missing member {}", name))),
+    };
+    // The required members are written in lexicographic order without whitespace
+    let canonical = match self.kty.as_str() {
+      "RSA" => format!(
+        r#"{{"e":{},"kty":"RSA","n":{}}}"#,
+        member("e", &self.e)?,
+        member("n", &self.n)?
+      ),
+      "EC" => format!(
+        r#"{{"crv":{},"kty":"EC","x":{},"y":{}}}"#,
+        member("crv", &self.crv)?,
+        member("x", &self.x)?,
+        member("y", &self.y)?
+      ),
+      "OKP" => format!(
+        r#"{{"crv":{},"kty":"OKP","x":{}}}"#,
+        member("crv", &self.crv)?,
+        member("x", &self.x)?
+      ),
+      kty => return Err(Error::InvalidJwk(format!("unsupported kty {}", kty))),
+    };
+
+    Ok(encode(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref()))
+  }
+}
+
+impl PublicKey {
+  /// Only plain RSA, EC and Ed25519 keys can be exported. Security keys are rejected, as a JWK can not carry
+  /// their application and would claim plain signatures.
+  pub fn to_jwk(&self) -> Result<Jwk> {
+    let mut jwk = Jwk {
+      kty: String::new(),
+      crv: None,
+      n: None,
+      e: None,
+      x: None,
+      y: None,
+      kid: None,
+      alg: None,
+    };
+
+    match self {
+      PublicKey::Ed25519(key) => {
+        jwk.kty = "OKP".to_string();
+        jwk.crv = Some("Ed25519".to_string());
+        jwk.x = Some(encode(key));
+      }
+      PublicKey::EcdsaP256(point) => ec_to_jwk(&mut jwk, "P-256", 32, point)?,
+      PublicKey::EcdsaP384(point) => ec_to_jwk(&mut jwk, "P-384", 48, point)?,
+      PublicKey::EcdsaP521(point) => ec_to_jwk(&mut jwk, "P-521", 66, point)?,
+      PublicKey::Rsa { e, n } => {
+        jwk.kty = "RSA".to_string();
+        jwk.n = Some(encode(strip_leading_zeros(n)));
+        jwk.e = Some(encode(strip_leading_zeros(e)));
+      }
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { .. } => return Err(Error::UnsupportedKeyType("ssh-dss".to_string())),
+      PublicKey::SkEcdsaP256 { .. }
+      | PublicKey::SkEd25519 { .. }
+      | PublicKey::Certificate(_)
+      | PublicKey::Unknown { .. } => return Err(Error::UnsupportedKeyType(self.algorithm().to_string())),
+    }
+
+    Ok(jwk)
+  }
+
+  pub fn from_jwk(jwk: &Jwk) -> Result<PublicKey> {
+    let member = |name: &str, value: &Option<String>| match value {
+      Some(value) => decode(value),
+      None => Err(Error::InvalidJwk(format!("missing member {}", name))),
+    };
+
+    match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+      ("OKP", Some("Ed25519")) => {
+        let x = member("x", &jwk.x)?;
+        if x.len() != ED25519_PUBLIC_KEY_LEN {
+          return Err(Error::InvalidKeyLength);
+        }
+        Ok(PublicKey::Ed25519(x))
+      }
+      ("EC", Some(crv)) => {
+        let x = member("x", &jwk.x)?;
+        let y = member("y", &jwk.y)?;
+        let coordinate_len = match crv {
+          "P-256" => 32,
+          "P-384" => 48,
+          "P-521" => 66,
+          _ => return Err(Error::InvalidJwk(format!("unsupported curve {}", crv))),
+        };
+        if x.len() != coordinate_len || y.len() != coordinate_len {
+          return Err(Error::InvalidKeyLength);
+        }
+        let point = [&[0x04], x.as_slice(), y.as_slice()].concat();
+
+        match crv {
+          "P-256" => Ok(PublicKey::EcdsaP256(point)),
+          "P-384" => Ok(PublicKey::EcdsaP384(point)),
+          _ => Ok(PublicKey::EcdsaP521(point)),
+        }
+      }
+      ("RSA", _) => Ok(PublicKey::Rsa {
+        e: to_mpint(&member("e", &jwk.e)?),
+        n: to_mpint(&member("n", &jwk.n)?),
+      }),
+      (kty, crv) => Err(Error::InvalidJwk(format!("unsupported key type {} {:?}", kty, crv))),
+    }
+  }
+
+  pub fn jwk_thumbprint(&self) -> Result<String> {
+    self.to_jwk()?.thumbprint()
+  }
+}
+
+fn ec_to_jwk(jwk: &mut Jwk, crv: &str, coordinate_len: usize, point: &[u8]) -> Result<()> {
+  // Uncompressed point: 0x04 || x || y
+  match point.first() {
+    Some(0x02) | Some(0x03) if point.len() == 1 + coordinate_len => return Err(Error::CompressedEcPoint),
+    _ if point.len() != 1 + 2 * coordinate_len => return Err(Error::InvalidKeyLength),
+    Some(0x04) => (),
+    _ => return Err(Error::InvalidEcPoint),
+  }
+  let (x, y) = point[1..].split_at(coordinate_len);
+
+  jwk.kty = "EC".to_string();
+  jwk.crv = Some(crv.to_string());
+  jwk.x = Some(encode(x));
+  jwk.y = Some(encode(y));
+
+  Ok(())
+}
+
+fn encode(data: &[u8]) -> String {
+  base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+fn decode(data: &str) -> Result<Vec<u8>> {
+  Ok(base64::decode_config(data, base64::URL_SAFE_NO_PAD)?)
+}
+
+fn strip_leading_zeros(value: &[u8]) -> &[u8] {
+  let first = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+  &value[first..]
+}
+
+// SSH mpints are two's complement, so a positive number with the high bit set needs a leading zero
+fn to_mpint(value: &[u8]) -> Vec<u8> {
+  let value = strip_leading_zeros(value);
+  match value.first() {
+    Some(b) if b & 0x80 != 0 => [&[0], value].concat(),
+    _ => value.to_vec(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn rfc7638_example() {
+    let jwk = Jwk::parse_json(
+      r#"{
+        "kty": "RSA",
+        "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+        "e": "AQAB",
+        "alg": "RS256",
+        "kid": "2011-04-29"
+      }"#,
+    )
+    .unwrap();
+
+    assert_eq!(jwk.thumbprint().unwrap(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+
+    let key = PublicKey::from_jwk(&jwk).unwrap();
+
+    assert_eq!(
+      key.jwk_thumbprint().unwrap(),
+      "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+    );
+  }
+
+  #[test]
+  fn thumbprint_escapes_members() {
+    let jwk = Jwk::parse_json(r#"{"kty":"RSA","e":"A\"B","n":"C\\D\u0001"}"#).unwrap();
+    let canonical = r#"{"e":"A\"B","kty":"RSA","n":"C\\D\u0001"}"#;
+
+    assert_eq!(
+      jwk.thumbprint().unwrap(),
+      encode(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+    );
+  }
+
+  #[test]
+  fn reject_malformed_ec_points() {
+    let point = match read_pub_key("unencrypted_ecdsa.pub") {
+      PublicKey::EcdsaP256(point) => point,
+      _ => panic!("Not an ecdsa key"),
+    };
+    let mut compressed = point[..33].to_vec();
+    compressed[0] = 0x02;
+    let mut wrong_prefix = point.clone();
+    wrong_prefix[0] = 0x05;
+
+    match PublicKey::EcdsaP256(compressed).to_jwk() {
+      Err(Error::CompressedEcPoint) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match PublicKey::EcdsaP256(wrong_prefix).to_jwk() {
+      Err(Error::InvalidEcPoint) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match PublicKey::EcdsaP384(point).to_jwk() {
+      Err(Error::InvalidKeyLength) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn reject_security_keys() {
+    for name in ["sk_ed25519.pub", "sk_ecdsa.pub"].iter() {
+      match read_pub_key(name).to_jwk() {
+        Err(Error::UnsupportedKeyType(_)) => (),
+        other => panic!("Unexpected result for {}: {:?}", name, other),
+      }
+    }
+  }
+
+  #[test]
+  fn fixture_roundtrip() {
+    for name in [
+      "unencrypted_rsa.pub",
+      "unencrypted_ecdsa.pub",
+      "unencrypted_ecdsa384.pub",
+      "unencrypted_ecdsa521.pub",
+      "unencrypted_ed25519.pub",
+    ]
+    .iter()
+    {
+      let key = read_pub_key(name);
+      let jwk = Jwk::parse_json(&key.to_jwk().unwrap().to_json()).unwrap();
+
+      assert_eq!(PublicKey::from_jwk(&jwk).unwrap().to_ssh_key(), key.to_ssh_key());
+    }
+  }
+
+  #[test]
+  fn ed25519_json() {
+    let jwk = read_pub_key("unencrypted_ed25519.pub").to_jwk().unwrap();
+
+    assert_eq!(
+      jwk.to_json(),
+      r#"{"kty":"OKP","crv":"Ed25519","x":"WJCkUxc1u605PjFnaFVzAFc3HJv59X470kDjbPTxNl4"}"#
+    );
+  }
+}
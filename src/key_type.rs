@@ -0,0 +1,248 @@
+use crate::error::{Error, Result};
+use crate::public::PublicKey;
+use std::fmt;
+use std::str::FromStr;
+
+/// The kinds of public keys this crate understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyType {
+  Ed25519,
+  EcdsaP256,
+  EcdsaP384,
+  EcdsaP521,
+  SkEcdsaP256,
+  SkEd25519,
+  Rsa,
+  #[cfg(feature = "legacy-dsa")]
+  Dsa,
+}
+
+impl KeyType {
+  /// All supported key types, in the order `ssh -Q key` lists them.
+  pub fn all() -> &'static [KeyType] {
+    &[
+      KeyType::Ed25519,
+      KeyType::SkEd25519,
+      KeyType::EcdsaP256,
+      KeyType::EcdsaP384,
+      KeyType::EcdsaP521,
+      KeyType::SkEcdsaP256,
+      KeyType::Rsa,
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa,
+    ]
+  }
+
+  /// Wire name, e.g. `ssh-ed25519`.
+  pub fn name(&self) -> &'static str {
+    match self {
+      KeyType::Ed25519 => "ssh-ed25519",
+      KeyType::EcdsaP256 => "ecdsa-sha2-nistp256",
+      KeyType::EcdsaP384 => "ecdsa-sha2-nistp384",
+      KeyType::EcdsaP521 => "ecdsa-sha2-nistp521",
+      KeyType::SkEcdsaP256 => "sk-ecdsa-sha2-nistp256@openssh.com",
+      KeyType::SkEd25519 => "sk-ssh-ed25519@openssh.com",
+      KeyType::Rsa => "ssh-rsa",
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => "ssh-dss",
+    }
+  }
+
+  /// Name as passed to `ssh-keygen -t`. The ECDSA curves share a name and are told apart by `-b`.
+  pub fn short_name(&self) -> &'static str {
+    match self {
+      KeyType::Ed25519 => "ed25519",
+      KeyType::EcdsaP256 | KeyType::EcdsaP384 | KeyType::EcdsaP521 => "ecdsa",
+      KeyType::SkEcdsaP256 => "ecdsa-sk",
+      KeyType::SkEd25519 => "ed25519-sk",
+      KeyType::Rsa => "rsa",
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => "dsa",
+    }
+  }
+
+  /// Type name as shown by `ssh-keygen -l`.
+  pub fn label(&self) -> &'static str {
+    match self {
+      KeyType::Ed25519 => "ED25519",
+      KeyType::EcdsaP256 | KeyType::EcdsaP384 | KeyType::EcdsaP521 => "ECDSA",
+      KeyType::SkEcdsaP256 => "ECDSA-SK",
+      KeyType::SkEd25519 => "ED25519-SK",
+      KeyType::Rsa => "RSA",
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => "DSA",
+    }
+  }
+
+  /// Wire name of certificates for this key type, e.g. `ssh-ed25519-cert-v01@openssh.com`.
+  pub fn certificate_name(&self) -> &'static str {
+    match self {
+      KeyType::Ed25519 => "ssh-ed25519-cert-v01@openssh.com",
+      KeyType::EcdsaP256 => "ecdsa-sha2-nistp256-cert-v01@openssh.com",
+      KeyType::EcdsaP384 => "ecdsa-sha2-nistp384-cert-v01@openssh.com",
+      KeyType::EcdsaP521 => "ecdsa-sha2-nistp521-cert-v01@openssh.com",
+      KeyType::SkEcdsaP256 => "sk-ecdsa-sha2-nistp256-cert-v01@openssh.com",
+      KeyType::SkEd25519 => "sk-ssh-ed25519-cert-v01@openssh.com",
+      KeyType::Rsa => "ssh-rsa-cert-v01@openssh.com",
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => "ssh-dss-cert-v01@openssh.com",
+    }
+  }
+
+  /// Certificate type name as shown by `ssh-keygen -l`.
+  pub fn certificate_label(&self) -> &'static str {
+    match self {
+      KeyType::Ed25519 => "ED25519-CERT",
+      KeyType::EcdsaP256 | KeyType::EcdsaP384 | KeyType::EcdsaP521 => "ECDSA-CERT",
+      KeyType::SkEcdsaP256 => "ECDSA-SK-CERT",
+      KeyType::SkEd25519 => "ED25519-SK-CERT",
+      KeyType::Rsa => "RSA-CERT",
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => "DSA-CERT",
+    }
+  }
+
+  /// Key size in bits for types with a fixed size, i.e. everything but RSA.
+  pub fn fixed_bits(&self) -> Option<u32> {
+    match self {
+      KeyType::Ed25519 | KeyType::EcdsaP256 | KeyType::SkEcdsaP256 | KeyType::SkEd25519 => Some(256),
+      KeyType::EcdsaP384 => Some(384),
+      KeyType::EcdsaP521 => Some(521),
+      KeyType::Rsa => None,
+      #[cfg(feature = "legacy-dsa")]
+      KeyType::Dsa => Some(1024),
+    }
+  }
+}
+
+impl fmt::Display for KeyType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+/// Accepts the wire names as well as the `ssh-keygen -t` names, where a plain `ecdsa` means P-256 like
+/// `ssh-keygen` defaults to.
+impl FromStr for KeyType {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<KeyType> {
+    match s {
+      "ecdsa" => return Ok(KeyType::EcdsaP256),
+      "rsa-sha2-256" | "rsa-sha2-512" => return Ok(KeyType::Rsa),
+      _ => (),
+    }
+    KeyType::all()
+      .iter()
+      .find(|key_type| key_type.name() == s || key_type.short_name() == s)
+      .copied()
+      .ok_or_else(|| Error::UnsupportedKeyType(s.to_string()))
+  }
+}
+
+impl PublicKey {
+  /// `None` for keys of unknown type. Certificates report the type of the certified key.
+  pub fn key_type(&self) -> Option<KeyType> {
+    match self {
+      PublicKey::Ed25519(_) => Some(KeyType::Ed25519),
+      PublicKey::EcdsaP256(_) => Some(KeyType::EcdsaP256),
+      PublicKey::EcdsaP384(_) => Some(KeyType::EcdsaP384),
+      PublicKey::EcdsaP521(_) => Some(KeyType::EcdsaP521),
+      PublicKey::SkEcdsaP256 { .. } => Some(KeyType::SkEcdsaP256),
+      PublicKey::SkEd25519 { .. } => Some(KeyType::SkEd25519),
+      PublicKey::Rsa { .. } => Some(KeyType::Rsa),
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { .. } => Some(KeyType::Dsa),
+      PublicKey::Certificate(cert) => cert.key.key_type(),
+      PublicKey::Unknown { .. } => None,
+    }
+  }
+
+  /// Approximate security level in bits (NIST SP 800-57 equivalents), 0 for unknown keys.
+  pub fn security_bits(&self) -> u32 {
+    match self.key_type() {
+      Some(KeyType::Rsa) => match self.bits() {
+        bits if bits >= 15360 => 256,
+        bits if bits >= 7680 => 192,
+        bits if bits >= 3072 => 128,
+        bits if bits >= 2048 => 112,
+        bits if bits >= 1024 => 80,
+        _ => 0,
+      },
+      #[cfg(feature = "legacy-dsa")]
+      Some(KeyType::Dsa) => 80,
+      Some(KeyType::EcdsaP521) => 256,
+      Some(_) => self.bits() / 2,
+      None => 0,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn parse_names() {
+    for key_type in KeyType::all() {
+      assert_eq!(key_type.name().parse::<KeyType>().unwrap(), *key_type);
+      assert_eq!(format!("{}", key_type).parse::<KeyType>().unwrap(), *key_type);
+    }
+    assert_eq!("ed25519".parse::<KeyType>().unwrap(), KeyType::Ed25519);
+    assert_eq!("ecdsa".parse::<KeyType>().unwrap(), KeyType::EcdsaP256);
+    assert_eq!("ed25519-sk".parse::<KeyType>().unwrap(), KeyType::SkEd25519);
+    assert_eq!("rsa".parse::<KeyType>().unwrap(), KeyType::Rsa);
+    assert!("ed448".parse::<KeyType>().is_err());
+  }
+
+  #[test]
+  fn fixture_metadata() {
+    let fixtures = [
+      ("unencrypted_rsa.pub", KeyType::Rsa, "ssh-rsa", 2048, 112),
+      (
+        "unencrypted_ecdsa.pub",
+        KeyType::EcdsaP256,
+        "ecdsa-sha2-nistp256",
+        256,
+        128,
+      ),
+      (
+        "unencrypted_ecdsa384.pub",
+        KeyType::EcdsaP384,
+        "ecdsa-sha2-nistp384",
+        384,
+        192,
+      ),
+      (
+        "unencrypted_ecdsa521.pub",
+        KeyType::EcdsaP521,
+        "ecdsa-sha2-nistp521",
+        521,
+        256,
+      ),
+      ("unencrypted_ed25519.pub", KeyType::Ed25519, "ssh-ed25519", 256, 128),
+      (
+        "sk_ed25519.pub",
+        KeyType::SkEd25519,
+        "sk-ssh-ed25519@openssh.com",
+        256,
+        128,
+      ),
+    ];
+
+    for (name, key_type, algorithm, bits, security_bits) in fixtures.iter() {
+      let key = read_pub_key(name);
+
+      assert_eq!(key.key_type(), Some(*key_type));
+      assert_eq!(key.algorithm(), *algorithm);
+      assert_eq!(key.bits(), *bits);
+      assert_eq!(key.security_bits(), *security_bits);
+      assert_eq!(key_type.fixed_bits().unwrap_or(*bits), *bits);
+    }
+  }
+}
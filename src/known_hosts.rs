@@ -0,0 +1,434 @@
+use crate::authorized_keys::is_blank_or_comment;
+use crate::error::{Error, Result};
+use crate::public::{PublicKey, PublicKeyLine};
+use ring::hmac;
+
+const HASH_MAGIC: &str = "|1|";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Marker {
+  CertAuthority,
+  Revoked,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HostPattern {
+  /// `|1|salt|hash` entry as written by `ssh-keygen -H`.
+  Hashed { salt: Vec<u8>, hash: Vec<u8> },
+  /// Plain (possibly wildcard) pattern, negated if it was prefixed with `!`.
+  Plain { pattern: String, negated: bool },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HostKeyStatus {
+  /// The host is known with this key.
+  Match,
+  /// The host is known, but with a different key of the same type.
+  Mismatch,
+  /// The key has been marked as `@revoked`.
+  Revoked,
+  /// There is no entry for the host with this key type.
+  Unknown,
+}
+
+#[derive(Debug)]
+pub struct KnownHost {
+  /// 1-based line number in the file the entry was read from.
+  pub line: usize,
+  pub marker: Option<Marker>,
+  pub hosts: Vec<HostPattern>,
+  pub key: PublicKey,
+  pub comment: Option<String>,
+}
+
+impl KnownHost {
+  pub fn parse_line(line_number: usize, line: &str) -> Result<KnownHost> {
+    let invalid = |msg: &str| Error::InvalidLine(line_number, msg.to_string());
+    let mut rest = line.trim();
+
+    let marker = if rest.starts_with('@') {
+      let (marker, remaining) = split_field(rest);
+      rest = remaining;
+      match marker {
+        "@cert-authority" => Some(Marker::CertAuthority),
+        "@revoked" => Some(Marker::Revoked),
+        _ => return Err(invalid("unknown marker")),
+      }
+    } else {
+      None
+    };
+    let (hosts, key_part) = split_field(rest);
+    if hosts.is_empty() || key_part.is_empty() {
+      return Err(invalid("missing host patterns or key"));
+    }
+    let hosts = parse_host_patterns(hosts).ok_or_else(|| invalid("invalid hashed host"))?;
+    let PublicKeyLine { key, comment, .. } =
+      PublicKeyLine::parse_or_unknown(key_part).map_err(|e| Error::InvalidLine(line_number, format!("{}", e)))?;
+
+    Ok(KnownHost {
+      line: line_number,
+      marker,
+      hosts,
+      key,
+      comment,
+    })
+  }
+
+  /// Check if the entry applies to `host` connected on `port`.
+  pub fn matches_host(&self, host: &str, port: u16) -> bool {
+    let name = lookup_name(host, port);
+    let mut matched = false;
+
+    for pattern in &self.hosts {
+      match pattern {
+        HostPattern::Hashed { salt, hash } => {
+          let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, salt);
+          if hmac::verify(&key, name.as_bytes(), hash).is_ok() {
+            matched = true;
+          }
+        }
+        HostPattern::Plain { pattern, negated } => {
+          if wildcard_match(&pattern.to_lowercase(), &name) {
+            if *negated {
+              return false;
+            }
+            matched = true;
+          }
+        }
+      }
+    }
+
+    matched
+  }
+}
+
+#[derive(Debug)]
+pub struct KnownHosts {
+  pub entries: Vec<KnownHost>,
+  /// Lines that were skipped because they could not be parsed, as `Error::InvalidLine`.
+  pub errors: Vec<Error>,
+}
+
+impl KnownHosts {
+  /// Parse all entries of a `known_hosts` file, skipping blank lines and comments. Like `ssh`, lines that can
+  /// not be parsed are skipped as well, keys of unknown type are kept as `PublicKey::Unknown`.
+  pub fn parse(content: &str) -> KnownHosts {
+    let mut known_hosts = KnownHosts {
+      entries: Vec::new(),
+      errors: Vec::new(),
+    };
+
+    for (idx, line) in content.lines().enumerate() {
+      if is_blank_or_comment(line) {
+        continue;
+      }
+      match KnownHost::parse_line(idx + 1, line) {
+        Ok(entry) => known_hosts.entries.push(entry),
+        Err(err) => known_hosts.errors.push(err),
+      }
+    }
+
+    known_hosts
+  }
+
+  /// All entries applying to `host` connected on `port`.
+  pub fn find<'a>(&'a self, host: &'a str, port: u16) -> impl Iterator<Item = &'a KnownHost> + 'a {
+    self.entries.iter().filter(move |entry| entry.matches_host(host, port))
+  }
+
+  /// Keys of the certificate authorities trusted for `host` connected on `port`.
+  pub fn cert_authorities<'a>(&'a self, host: &'a str, port: u16) -> impl Iterator<Item = &'a PublicKey> + 'a {
+    self
+      .find(host, port)
+      .filter(|entry| entry.marker == Some(Marker::CertAuthority))
+      .map(|entry| &entry.key)
+  }
+
+  /// Check the host key presented by `host` connected on `port`, like `ssh` does on connect.
+  ///
+  /// A host certificate is a `Match` if it is signed by one of the `@cert-authority` keys for the host, and
+  /// `Revoked` if either the certified key or its signing key is marked as `@revoked`. Only the signing key is
+  /// checked here, the certificate itself still has to be validated with a `CertValidator`.
+  pub fn check(&self, host: &str, port: u16, key: &PublicKey) -> HostKeyStatus {
+    if let PublicKey::Certificate(cert) = key {
+      return self.check_certificate(host, port, &cert.key, &cert.signature_key);
+    }
+
+    let blob = key.to_ssh_key();
+    let mut status = HostKeyStatus::Unknown;
+
+    for entry in self.find(host, port) {
+      let same_type = entry.key.algorithm() == key.algorithm();
+      let same_key = same_type && entry.key.to_ssh_key() == blob;
+
+      match entry.marker {
+        Some(Marker::Revoked) if same_key => return HostKeyStatus::Revoked,
+        Some(_) => (),
+        None if same_key => status = HostKeyStatus::Match,
+        None if same_type && status == HostKeyStatus::Unknown => status = HostKeyStatus::Mismatch,
+        None => (),
+      }
+    }
+
+    status
+  }
+
+  fn check_certificate(&self, host: &str, port: u16, key: &PublicKey, ca_key: &PublicKey) -> HostKeyStatus {
+    let key_blob = key.to_ssh_key();
+    let ca_blob = ca_key.to_ssh_key();
+    let mut status = HostKeyStatus::Unknown;
+
+    for entry in self.find(host, port) {
+      let blob = entry.key.to_ssh_key();
+
+      match entry.marker {
+        Some(Marker::Revoked) if blob == key_blob || blob == ca_blob => return HostKeyStatus::Revoked,
+        Some(Marker::CertAuthority) if blob == ca_blob => status = HostKeyStatus::Match,
+        _ => (),
+      }
+    }
+
+    status
+  }
+}
+
+fn split_field(s: &str) -> (&str, &str) {
+  match s.find([' ', '\t']) {
+    Some(idx) => (&s[..idx], s[idx..].trim_start()),
+    None => (s, ""),
+  }
+}
+
+fn parse_host_patterns(hosts: &str) -> Option<Vec<HostPattern>> {
+  if let Some(hashed) = hosts.strip_prefix(HASH_MAGIC) {
+    let mut parts = hashed.splitn(2, '|');
+    let salt = base64::decode_config(parts.next()?, base64::STANDARD).ok()?;
+    let hash = base64::decode_config(parts.next()?, base64::STANDARD).ok()?;
+
+    return Some(vec![HostPattern::Hashed { salt, hash }]);
+  }
+
+  Some(
+    hosts
+      .split(',')
+      .filter(|pattern| !pattern.is_empty())
+      .map(|pattern| match pattern.strip_prefix('!') {
+        Some(pattern) => HostPattern::Plain {
+          pattern: pattern.to_string(),
+          negated: true,
+        },
+        None => HostPattern::Plain {
+          pattern: pattern.to_string(),
+          negated: false,
+        },
+      })
+      .collect(),
+  )
+}
+
+// Hosts on a non-standard port are recorded as `[host]:port`
+fn lookup_name(host: &str, port: u16) -> String {
+  if port == 22 {
+    host.to_lowercase()
+  } else {
+    format!("[{}]:{}", host.to_lowercase(), port)
+  }
+}
+
+/// Match `name` against a pattern with `*` and `?` wildcards.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+  let pattern = pattern.as_bytes();
+  let name = name.as_bytes();
+  let (mut p, mut n) = (0, 0);
+  let mut backtrack = None;
+
+  while n < name.len() {
+    if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+      p += 1;
+      n += 1;
+    } else if p < pattern.len() && pattern[p] == b'*' {
+      backtrack = Some((p, n));
+      p += 1;
+    } else if let Some((star, matched)) = backtrack {
+      p = star + 1;
+      n = matched + 1;
+      backtrack = Some((star, matched + 1));
+    } else {
+      return false;
+    }
+  }
+  pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn fixture_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  fn known_hosts() -> KnownHosts {
+    KnownHosts::parse(&fs::read_to_string("fixtures/known_hosts").unwrap())
+  }
+
+  #[test]
+  fn parse_fixture() {
+    let known_hosts = known_hosts();
+
+    assert_eq!(known_hosts.entries.len(), 7);
+    assert!(known_hosts.errors.is_empty());
+    assert_eq!(known_hosts.entries[0].line, 2);
+    match &known_hosts.entries[0].hosts[0] {
+      HostPattern::Hashed { salt, hash } => {
+        assert_eq!(salt.len(), 20);
+        assert_eq!(hash.len(), 20);
+      }
+      _ => panic!("Not a hashed host"),
+    }
+    assert_eq!(known_hosts.entries[3].comment.as_deref(), Some("bastion key"));
+    assert_eq!(known_hosts.entries[4].marker, Some(Marker::CertAuthority));
+    assert_eq!(known_hosts.entries[5].marker, Some(Marker::Revoked));
+  }
+
+  #[test]
+  fn check_host_keys() {
+    let known_hosts = known_hosts();
+    let ed25519 = fixture_key("unencrypted_ed25519.pub");
+    let ecdsa = fixture_key("unencrypted_ecdsa.pub");
+    let ecdsa384 = fixture_key("unencrypted_ecdsa384.pub");
+    let rsa = fixture_key("unencrypted_rsa.pub");
+
+    assert_eq!(
+      known_hosts.check("hashed.example.com", 22, &ed25519),
+      HostKeyStatus::Match
+    );
+    assert_eq!(
+      known_hosts.check("Hashed.Example.com", 22, &ed25519),
+      HostKeyStatus::Match
+    );
+    assert_eq!(
+      known_hosts.check("hashed.example.com", 22, &ecdsa),
+      HostKeyStatus::Unknown
+    );
+    assert_eq!(
+      known_hosts.check("hashed.example.com", 2222, &ecdsa),
+      HostKeyStatus::Match
+    );
+    assert_eq!(
+      known_hosts.check("hashed.example.com", 2222, &ed25519),
+      HostKeyStatus::Unknown
+    );
+
+    assert_eq!(
+      known_hosts.check("db.internal.example.com", 22, &rsa),
+      HostKeyStatus::Match
+    );
+    assert_eq!(
+      known_hosts.check("bastion.internal.example.com", 22, &rsa),
+      HostKeyStatus::Unknown
+    );
+    assert_eq!(
+      known_hosts.check("bastion.internal.example.com", 22, &ecdsa384),
+      HostKeyStatus::Match
+    );
+    assert_eq!(known_hosts.check("10.0.0.1", 22, &ecdsa384), HostKeyStatus::Match);
+    assert_eq!(known_hosts.check("10.0.0.1", 2022, &ecdsa384), HostKeyStatus::Unknown);
+
+    assert_eq!(
+      known_hosts.check("revoked.example.com", 22, &ecdsa),
+      HostKeyStatus::Revoked
+    );
+    assert_eq!(
+      known_hosts.check("other.example.com", 22, &ed25519),
+      HostKeyStatus::Unknown
+    );
+    assert_eq!(known_hosts.cert_authorities("other.example.com", 22).count(), 1);
+  }
+
+  #[test]
+  fn check_mismatch() {
+    let rsa_line = fs::read_to_string("fixtures/unencrypted_rsa.pub").unwrap();
+    let other_rsa = PublicKey::parse_raw(&{
+      let mut blob = fixture_key("unencrypted_rsa.pub").to_ssh_key();
+      let last = blob.len() - 1;
+      blob[last] ^= 1;
+      blob
+    })
+    .unwrap();
+    let known_hosts = KnownHosts::parse(&format!("host.example.com {}", rsa_line));
+
+    assert_eq!(
+      known_hosts.check("host.example.com", 22, &other_rsa),
+      HostKeyStatus::Mismatch
+    );
+  }
+
+  #[test]
+  fn check_host_certificates() {
+    let host_cert = fixture_key("certs/rsa-cert.pub");
+    let ca_line = fs::read_to_string("fixtures/certs/ca_rsa.pub").unwrap();
+    let known_hosts = KnownHosts::parse(&format!("@cert-authority *.example.com {}", ca_line.trim()));
+
+    assert_eq!(
+      known_hosts.check("host.example.com", 22, &host_cert),
+      HostKeyStatus::Match
+    );
+    assert_eq!(
+      known_hosts.check("host.example.org", 22, &host_cert),
+      HostKeyStatus::Unknown
+    );
+
+    let known_hosts = KnownHosts::parse(&format!(
+      "@cert-authority *.example.com {}\n@revoked * {}",
+      ca_line.trim(),
+      ca_line.trim()
+    ));
+    assert_eq!(
+      known_hosts.check("host.example.com", 22, &host_cert),
+      HostKeyStatus::Revoked
+    );
+  }
+
+  #[test]
+  fn skip_invalid_lines() {
+    let ed25519_line = fs::read_to_string("fixtures/unencrypted_ed25519.pub").unwrap();
+    let mut unknown = crate::encoding::Writer::new();
+    unknown.write_string(b"unknown@example.com");
+    unknown.write_string(b"opaque");
+    let content = format!(
+      "|1|not base64|!! {}\nvendor.example.com unknown@example.com {}\n@bogus host {}\nhost.example.com {}",
+      ed25519_line.trim(),
+      base64::encode(&unknown.buffer),
+      ed25519_line.trim(),
+      ed25519_line.trim()
+    );
+    let known_hosts = KnownHosts::parse(&content);
+
+    assert_eq!(known_hosts.entries.len(), 2);
+    match &known_hosts.entries[0].key {
+      PublicKey::Unknown { algorithm, .. } => assert_eq!(algorithm, "unknown@example.com"),
+      other => panic!("Not an unknown key: {:?}", other),
+    }
+    assert_eq!(known_hosts.entries[1].line, 4);
+    match known_hosts.errors.as_slice() {
+      [Error::InvalidLine(1, _), Error::InvalidLine(3, _)] => (),
+      other => panic!("Unexpected errors: {:?}", other),
+    }
+    assert_eq!(
+      known_hosts.check("host.example.com", 22, &fixture_key("unencrypted_ed25519.pub")),
+      HostKeyStatus::Match
+    );
+  }
+
+  #[test]
+  fn wildcards() {
+    assert!(wildcard_match("*", "anything"));
+    assert!(wildcard_match("*.example.com", "a.b.example.com"));
+    assert!(wildcard_match("host?.example.com", "host1.example.com"));
+    assert!(!wildcard_match("host?.example.com", "host12.example.com"));
+    assert!(wildcard_match("[host*]:2222", "[host1]:2222"));
+    assert!(!wildcard_match("*.example.com", "example.com"));
+  }
+}
@@ -1,15 +1,46 @@
 pub mod agent;
+pub mod authorized_keys;
+pub mod known_hosts;
 
+mod cert_authority;
+mod cert_builder;
+mod cert_listing;
+mod cert_options;
+mod cert_validator;
+mod certificate;
 mod encoding;
 mod error;
+#[cfg(feature = "jwk")]
+mod jwk;
+mod key_type;
+mod md5;
 mod mini_der;
+#[cfg(feature = "legacy-dsa")]
+mod mini_dsa;
+mod mini_ec;
+mod pkix;
+mod policy;
 #[cfg(feature = "with-private")]
 mod private;
 mod public;
+mod randomart;
+mod rfc4716;
+#[cfg(feature = "serde")]
+mod serde_support;
 mod signature;
 
+pub use cert_authority::*;
+pub use cert_builder::*;
+pub use cert_options::*;
+pub use cert_validator::*;
+pub use certificate::*;
 pub use error::*;
+#[cfg(feature = "jwk")]
+pub use jwk::*;
+pub use key_type::*;
+pub use policy::*;
 #[cfg(feature = "with-private")]
 pub use private::*;
 pub use public::*;
+pub use rfc4716::*;
 pub use signature::*;
@@ -1,4 +1,9 @@
+use crate::error::{Error, Result};
+
 const TAG_INTEGER: u8 = 0x2;
+const TAG_BIT_STRING: u8 = 0x3;
+pub const TAG_NULL: u8 = 0x5;
+pub const TAG_OID: u8 = 0x6;
 const TAG_SEQUENCE: u8 = 0x30;
 
 fn length_length(length: usize) -> u8 {
@@ -61,3 +66,108 @@ pub fn encode_ecdsa_sig(r: &[u8], s: &[u8]) -> Vec<u8> {
 
   der
 }
+
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+  let mut der = Vec::with_capacity(block_length(content.len()));
+
+  encode_tag(&mut der, tag, content.len());
+  der.extend_from_slice(content);
+
+  der
+}
+
+pub fn encode_sequence(parts: &[&[u8]]) -> Vec<u8> {
+  encode(TAG_SEQUENCE, &parts.concat())
+}
+
+/// Encode an unsigned big endian number as INTEGER.
+pub fn encode_unsigned_integer(value: &[u8]) -> Vec<u8> {
+  let first = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+  let value = &value[first..];
+
+  if value.is_empty() {
+    encode(TAG_INTEGER, &[0])
+  } else if value[0] & 0x80 != 0 {
+    encode(TAG_INTEGER, &[&[0], value].concat())
+  } else {
+    encode(TAG_INTEGER, value)
+  }
+}
+
+pub fn encode_bit_string(value: &[u8]) -> Vec<u8> {
+  encode(TAG_BIT_STRING, &[&[0], value].concat())
+}
+
+pub struct DerReader<'a> {
+  data: &'a [u8],
+}
+
+impl<'a> DerReader<'a> {
+  pub fn new(data: &'a [u8]) -> DerReader<'a> {
+    DerReader { data }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.data.is_empty()
+  }
+
+  pub fn peek_tag(&self) -> Option<u8> {
+    self.data.first().cloned()
+  }
+
+  /// Read the content of the next element, which is required to have the given tag.
+  pub fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+    if self.data.len() < 2 || self.data[0] != tag {
+      return Err(Error::CouldNotReadKey);
+    }
+    let (length, header) = match self.data[1] {
+      n if n < 0x80 => (n as usize, 2),
+      n => {
+        let num_bytes = (n & 0x7f) as usize;
+        if num_bytes == 0 || num_bytes > 4 || self.data.len() < 2 + num_bytes {
+          return Err(Error::CouldNotReadKey);
+        }
+        let length = self.data[2..2 + num_bytes]
+          .iter()
+          .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (length, 2 + num_bytes)
+      }
+    };
+    let end = header.checked_add(length).ok_or(Error::BufferTooShort)?;
+    if self.data.len() < end {
+      return Err(Error::BufferTooShort);
+    }
+    let content = &self.data[header..end];
+    self.data = &self.data[end..];
+
+    Ok(content)
+  }
+
+  pub fn read_sequence(&mut self) -> Result<DerReader<'a>> {
+    Ok(DerReader::new(self.read(TAG_SEQUENCE)?))
+  }
+
+  pub fn read_integer(&mut self) -> Result<&'a [u8]> {
+    self.read(TAG_INTEGER)
+  }
+
+  /// Read an INTEGER that has to be non-negative, keeping a leading zero byte like an SSH mpint.
+  pub fn read_unsigned_integer(&mut self) -> Result<&'a [u8]> {
+    match self.read_integer()? {
+      value @ [first, ..] if first & 0x80 == 0 => Ok(value),
+      _ => Err(Error::CouldNotReadKey),
+    }
+  }
+
+  pub fn read_oid(&mut self) -> Result<&'a [u8]> {
+    self.read(TAG_OID)
+  }
+
+  /// Read a BIT STRING without unused bits.
+  pub fn read_bit_string(&mut self) -> Result<&'a [u8]> {
+    match self.read(TAG_BIT_STRING)? {
+      [0, value @ ..] => Ok(value),
+      _ => Err(Error::CouldNotReadKey),
+    }
+  }
+}
@@ -0,0 +1,75 @@
+// DSA signature verification as used by the legacy `ssh-dss` keys (FIPS 186-2 with SHA-1).
+//
+// Neither ring nor any other dependency covers DSA anymore. Like mini_ec this only ever sees public data,
+// so there is no need to be constant time.
+
+use crate::public::mpint_bits;
+use num_bigint::BigUint;
+
+/// The only sizes OpenSSH accepts for `ssh-dss` keys.
+pub const P_BITS: u32 = 1024;
+pub const Q_BITS: u32 = 160;
+
+/// Verify a DSA signature (r, s) over an already hashed message.
+pub fn verify(p: &[u8], q: &[u8], g: &[u8], y: &[u8], digest: &[u8], r: &[u8], s: &[u8]) -> bool {
+  // Keys are untrusted, so bound the cost of modpow before doing any arithmetic
+  if mpint_bits(p) != P_BITS || mpint_bits(q) != Q_BITS {
+    return false;
+  }
+
+  let p = BigUint::from_bytes_be(p);
+  let q = BigUint::from_bytes_be(q);
+  let g = BigUint::from_bytes_be(g);
+  let y = BigUint::from_bytes_be(y);
+  let r = BigUint::from_bytes_be(r);
+  let s = BigUint::from_bytes_be(s);
+  let zero = BigUint::from(0u32);
+  let one = BigUint::from(1u32);
+
+  if q < BigUint::from(2u32) || p <= q || g <= one || g >= p || y <= one || y >= p {
+    return false;
+  }
+  if r == zero || s == zero || r >= q || s >= q {
+    return false;
+  }
+
+  // Use the leftmost bits of the digest if it is longer than q
+  let mut e = BigUint::from_bytes_be(digest);
+  let digest_bits = digest.len() as u64 * 8;
+  if digest_bits > q.bits() {
+    e >>= digest_bits - q.bits();
+  }
+
+  // q is prime, so s^-1 = s^(q-2)
+  let w = s.modpow(&(&q - 2u32), &q);
+  let u1 = e * &w % &q;
+  let u2 = &r * &w % &q;
+  let v = g.modpow(&u1, &p) * y.modpow(&u2, &p) % &p % &q;
+
+  v == r
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::public::PublicKey;
+  use std::fs;
+
+  #[test]
+  fn reject_oversized_keys() {
+    let line = fs::read_to_string("fixtures/unencrypted_dsa.pub").unwrap();
+    let (p, q, g, y) = match PublicKey::parse_pub(&line).unwrap() {
+      PublicKey::Dsa { p, q, g, y } => (p, q, g, y),
+      _ => panic!("Not a dsa key"),
+    };
+    let (r, s) = ([1u8; 20], [1u8; 20]);
+    let mut large_p = vec![0xff; 1024 * 1024];
+    large_p.extend_from_slice(&p);
+    let mut large_q = vec![0xff; 8];
+    large_q.extend_from_slice(&q);
+
+    assert!(!verify(&large_p, &q, &g, &y, &[0; 20], &r, &s));
+    assert!(!verify(&p, &large_q, &g, &y, &[0; 20], &r, &s));
+    assert!(!verify(&p[..p.len() - 1], &q, &g, &y, &[0; 20], &r, &s));
+  }
+}
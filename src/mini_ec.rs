@@ -0,0 +1,323 @@
+// Minimal arithmetic on the NIST prime curves (all of them have a = -3).
+//
+// ring covers ECDSA verification for P-256 and P-384, this fills the gap for P-521 and is used to check
+// that points are actually on their curve. Nothing in here handles secret data, so there is no need to be
+// constant time.
+
+use num_bigint::BigUint;
+
+pub struct Curve {
+  p: BigUint,
+  b: BigUint,
+  n: BigUint,
+  gx: BigUint,
+  gy: BigUint,
+  /// Size of a field element in bytes
+  pub field_len: usize,
+}
+
+fn hex(s: &str) -> BigUint {
+  BigUint::parse_bytes(s.as_bytes(), 16).unwrap()
+}
+
+impl Curve {
+  pub fn p256() -> Curve {
+    Curve {
+      p: hex("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff"),
+      b: hex("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b"),
+      n: hex("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+      gx: hex("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+      gy: hex("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+      field_len: 32,
+    }
+  }
+
+  pub fn p384() -> Curve {
+    Curve {
+      p: hex(concat!(
+        "fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffe",
+        "ffffffff0000000000000000ffffffff"
+      )),
+      b: hex(concat!(
+        "b3312fa7e23ee7e4988e056be3f82d19181d9c6efe8141120314088f5013875a",
+        "c656398d8a2ed19d2a85c8edd3ec2aef"
+      )),
+      n: hex(concat!(
+        "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf",
+        "581a0db248b0a77aecec196accc52973"
+      )),
+      gx: hex(concat!(
+        "aa87ca22be8b05378eb1c71ef320ad746e1d3b628ba79b9859f741e082542a38",
+        "5502f25dbf55296c3a545e3872760ab7"
+      )),
+      gy: hex(concat!(
+        "3617de4a96262c6f5d9e98bf9292dc29f8f41dbd289a147ce9da3113b5f0b8c0",
+        "0a60b1ce1d7e819d7a431d7c90ea0e5f"
+      )),
+      field_len: 48,
+    }
+  }
+
+  pub fn p521() -> Curve {
+    Curve {
+      p: (BigUint::from(1u32) << 521) - 1u32,
+      b: hex(concat!(
+        "0051953eb9618e1c9a1f929a21a0b68540eea2da725b99b315f3b8b489918ef1",
+        "09e156193951ec7e937b1652c0bd3bb1bf073573df883d2c34f1ef451fd46b50",
+        "3f00"
+      )),
+      n: hex(concat!(
+        "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+        "fffa51868783bf2f966b7fcc0148f709a5d03bb5c9b8899c47aebb6fb71e9138",
+        "6409"
+      )),
+      gx: hex(concat!(
+        "00c6858e06b70404e9cd9e3ecb662395b4429c648139053fb521f828af606b4d",
+        "3dbaa14b5e77efe75928fe1dc127a2ffa8de3348b3c1856a429bf97e7e31c2e5",
+        "bd66"
+      )),
+      gy: hex(concat!(
+        "011839296a789a3bc0045c8a5fb42c7d1bd998f54449579b446817afbd17273e",
+        "662c97ee72995ef42640c550b9013fad0761353c7086a272c24088be94769fd1",
+        "6650"
+      )),
+      field_len: 66,
+    }
+  }
+
+  /// Decode an uncompressed SEC1 point, checking that it is on the curve.
+  pub fn decode_point(&self, point: &[u8]) -> Option<(BigUint, BigUint)> {
+    if point.len() != 1 + 2 * self.field_len || point[0] != 0x04 {
+      return None;
+    }
+    let x = BigUint::from_bytes_be(&point[1..=self.field_len]);
+    let y = BigUint::from_bytes_be(&point[1 + self.field_len..]);
+
+    if x >= self.p || y >= self.p {
+      return None;
+    }
+    // y^2 = x^3 - 3x + b
+    let lhs = &y * &y % &self.p;
+    let rhs = (&x * &x * &x + &self.b + &self.p * 3u32 - &x * 3u32) % &self.p;
+
+    if lhs == rhs {
+      Some((x, y))
+    } else {
+      None
+    }
+  }
+
+  /// Verify an ECDSA signature (r, s) over an already hashed message.
+  pub fn verify(&self, point: &[u8], digest: &[u8], r: &[u8], s: &[u8]) -> bool {
+    let (qx, qy) = match self.decode_point(point) {
+      Some(q) => q,
+      None => return false,
+    };
+    let r = BigUint::from_bytes_be(r);
+    let s = BigUint::from_bytes_be(s);
+    let zero = BigUint::from(0u32);
+
+    if r == zero || s == zero || r >= self.n || s >= self.n {
+      return false;
+    }
+
+    // Use the leftmost bits of the digest if it is longer than the group order
+    let mut e = BigUint::from_bytes_be(digest);
+    let digest_bits = digest.len() as u64 * 8;
+    if digest_bits > self.n.bits() {
+      e >>= digest_bits - self.n.bits();
+    }
+
+    // n is prime, so s^-1 = s^(n-2)
+    let w = s.modpow(&(&self.n - 2u32), &self.n);
+    let u1 = e * &w % &self.n;
+    let u2 = &r * &w % &self.n;
+
+    let g = Jacobian::from_affine(self.gx.clone(), self.gy.clone());
+    let q = Jacobian::from_affine(qx, qy);
+    let sum = self.multiply_add(&g, &u1, &q, &u2);
+
+    match self.to_affine(&sum) {
+      Some((x, _)) => x % &self.n == r,
+      None => false,
+    }
+  }
+
+  fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+    (a + &self.p - b) % &self.p
+  }
+
+  fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+    a * b % &self.p
+  }
+
+  fn double(&self, point: &Jacobian) -> Jacobian {
+    if point.is_infinity() {
+      return point.clone();
+    }
+    let Jacobian { x, y, z } = point;
+    let delta = self.mul(z, z);
+    let gamma = self.mul(y, y);
+    let beta = self.mul(x, &gamma);
+    let alpha = self.mul(&(self.sub(x, &delta) * 3u32), &(x + &delta));
+    let x3 = self.sub(&self.mul(&alpha, &alpha), &(&beta * 8u32 % &self.p));
+    let yz = (y + z) % &self.p;
+    let z3 = self.sub(&self.sub(&self.mul(&yz, &yz), &gamma), &delta);
+    let y3 = self.sub(
+      &self.mul(&alpha, &self.sub(&(&beta * 4u32 % &self.p), &x3)),
+      &(self.mul(&gamma, &gamma) * 8u32 % &self.p),
+    );
+
+    Jacobian { x: x3, y: y3, z: z3 }
+  }
+
+  fn add(&self, a: &Jacobian, b: &Jacobian) -> Jacobian {
+    if a.is_infinity() {
+      return b.clone();
+    }
+    if b.is_infinity() {
+      return a.clone();
+    }
+    let z1z1 = self.mul(&a.z, &a.z);
+    let z2z2 = self.mul(&b.z, &b.z);
+    let u1 = self.mul(&a.x, &z2z2);
+    let u2 = self.mul(&b.x, &z1z1);
+    let s1 = self.mul(&self.mul(&a.y, &b.z), &z2z2);
+    let s2 = self.mul(&self.mul(&b.y, &a.z), &z1z1);
+    let h = self.sub(&u2, &u1);
+    let r = self.sub(&s2, &s1) * 2u32 % &self.p;
+    let zero = BigUint::from(0u32);
+
+    if h == zero {
+      return if r == zero {
+        self.double(a)
+      } else {
+        Jacobian::infinity()
+      };
+    }
+    let h2 = &h * 2u32 % &self.p;
+    let i = self.mul(&h2, &h2);
+    let j = self.mul(&h, &i);
+    let v = self.mul(&u1, &i);
+    let x3 = self.sub(&self.sub(&self.mul(&r, &r), &j), &(&v * 2u32 % &self.p));
+    let y3 = self.sub(&self.mul(&r, &self.sub(&v, &x3)), &(self.mul(&s1, &j) * 2u32 % &self.p));
+    let z12 = (&a.z + &b.z) % &self.p;
+    let z3 = self.mul(&self.sub(&self.sub(&self.mul(&z12, &z12), &z1z1), &z2z2), &h);
+
+    Jacobian { x: x3, y: y3, z: z3 }
+  }
+
+  /// Compute a * u + b * v with a single run of doublings (Shamir's trick).
+  fn multiply_add(&self, a: &Jacobian, u: &BigUint, b: &Jacobian, v: &BigUint) -> Jacobian {
+    let ab = self.add(a, b);
+    let mut result = Jacobian::infinity();
+
+    for i in (0..u.bits().max(v.bits())).rev() {
+      result = self.double(&result);
+      match (u.bit(i), v.bit(i)) {
+        (true, true) => result = self.add(&result, &ab),
+        (true, false) => result = self.add(&result, a),
+        (false, true) => result = self.add(&result, b),
+        (false, false) => (),
+      }
+    }
+
+    result
+  }
+
+  fn to_affine(&self, point: &Jacobian) -> Option<(BigUint, BigUint)> {
+    if point.is_infinity() {
+      return None;
+    }
+    let z_inv = point.z.modpow(&(&self.p - 2u32), &self.p);
+    let z_inv2 = self.mul(&z_inv, &z_inv);
+
+    Some((
+      self.mul(&point.x, &z_inv2),
+      self.mul(&point.y, &self.mul(&z_inv2, &z_inv)),
+    ))
+  }
+}
+
+#[derive(Clone)]
+struct Jacobian {
+  x: BigUint,
+  y: BigUint,
+  z: BigUint,
+}
+
+impl Jacobian {
+  fn from_affine(x: BigUint, y: BigUint) -> Jacobian {
+    Jacobian {
+      x,
+      y,
+      z: BigUint::from(1u32),
+    }
+  }
+
+  fn infinity() -> Jacobian {
+    Jacobian {
+      x: BigUint::from(1u32),
+      y: BigUint::from(1u32),
+      z: BigUint::from(0u32),
+    }
+  }
+
+  fn is_infinity(&self) -> bool {
+    self.z == BigUint::from(0u32)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ring::rand::SystemRandom;
+  use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING};
+  use ring::{digest, signature};
+
+  fn check_against_ring(
+    curve: &Curve,
+    alg: &'static signature::EcdsaSigningAlgorithm,
+    hash: &'static digest::Algorithm,
+  ) {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(alg, &rng).unwrap();
+    let key_pair = EcdsaKeyPair::from_pkcs8(alg, pkcs8.as_ref()).unwrap();
+    let point = key_pair.public_key().as_ref();
+    let message = b"mini_ec test message";
+    let sig = key_pair.sign(&rng, message).unwrap();
+    let (r, s) = sig.as_ref().split_at(curve.field_len);
+    let digest = digest::digest(hash, message);
+
+    assert!(curve.decode_point(point).is_some());
+    assert!(curve.verify(point, digest.as_ref(), r, s));
+    assert!(!curve.verify(point, digest::digest(hash, b"other").as_ref(), r, s));
+    assert!(!curve.verify(point, digest.as_ref(), s, r));
+  }
+
+  #[test]
+  fn verify_p256() {
+    check_against_ring(&Curve::p256(), &ECDSA_P256_SHA256_FIXED_SIGNING, &digest::SHA256);
+  }
+
+  #[test]
+  fn verify_p384() {
+    check_against_ring(&Curve::p384(), &ECDSA_P384_SHA384_FIXED_SIGNING, &digest::SHA384);
+  }
+
+  #[test]
+  fn p521_generator_on_curve() {
+    let curve = Curve::p521();
+    let pad = |v: &BigUint| {
+      let bytes = v.to_bytes_be();
+      [vec![0u8; curve.field_len - bytes.len()], bytes].concat()
+    };
+    let point = [vec![0x04], pad(&curve.gx), pad(&curve.gy)].concat();
+
+    assert!(curve.decode_point(&point).is_some());
+
+    let mut point = point;
+    point[10] ^= 1;
+    assert!(curve.decode_point(&point).is_none());
+  }
+}
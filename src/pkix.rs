@@ -0,0 +1,249 @@
+use crate::error::{Error, Result};
+use crate::mini_der::{self, DerReader};
+use crate::public::PublicKey;
+use ring::signature::ED25519_PUBLIC_KEY_LEN;
+
+// Content bytes of the OIDs used in SubjectPublicKeyInfo
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]; // 1.2.840.113549.1.1.1
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]; // 1.2.840.10045.2.1
+const OID_PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07]; // 1.2.840.10045.3.1.7
+const OID_SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22]; // 1.3.132.0.34
+const OID_SECP521R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23]; // 1.3.132.0.35
+const OID_ED25519: &[u8] = &[0x2b, 0x65, 0x70]; // 1.3.101.112
+#[cfg(feature = "legacy-dsa")]
+const OID_DSA: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x38, 0x04, 0x01]; // 1.2.840.10040.4.1
+
+const PEM_BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+const PEM_END: &str = "-----END PUBLIC KEY-----";
+const PEM_LINE_LENGTH: usize = 64;
+
+impl PublicKey {
+  /// Parse a DER encoded X.509 SubjectPublicKeyInfo.
+  pub fn parse_pkix_der(der: &[u8]) -> Result<PublicKey> {
+    let mut outer = DerReader::new(der);
+    let mut spki = outer.read_sequence()?;
+    let mut algorithm = spki.read_sequence()?;
+    let oid = algorithm.read_oid()?;
+    let public_key = spki.read_bit_string()?;
+
+    if !outer.is_empty() || !spki.is_empty() {
+      return Err(Error::CouldNotReadKey);
+    }
+
+    match oid {
+      OID_RSA_ENCRYPTION => {
+        if algorithm.peek_tag() == Some(mini_der::TAG_NULL) {
+          algorithm.read(mini_der::TAG_NULL)?;
+        }
+        let mut rsa_key = DerReader::new(public_key).read_sequence()?;
+        let n = Vec::from(rsa_key.read_unsigned_integer()?);
+        let e = Vec::from(rsa_key.read_unsigned_integer()?);
+
+        Ok(PublicKey::Rsa { e, n })
+      }
+      OID_EC_PUBLIC_KEY => match algorithm.read_oid()? {
+        OID_PRIME256V1 => Ok(PublicKey::EcdsaP256(Vec::from(public_key))),
+        OID_SECP384R1 => Ok(PublicKey::EcdsaP384(Vec::from(public_key))),
+        OID_SECP521R1 => Ok(PublicKey::EcdsaP521(Vec::from(public_key))),
+        _ => Err(Error::CouldNotReadKey),
+      },
+      OID_ED25519 if public_key.len() == ED25519_PUBLIC_KEY_LEN => Ok(PublicKey::Ed25519(Vec::from(public_key))),
+      OID_ED25519 => Err(Error::InvalidKeyLength),
+      #[cfg(feature = "legacy-dsa")]
+      OID_DSA => {
+        let mut params = algorithm.read_sequence()?;
+        let p = Vec::from(params.read_unsigned_integer()?);
+        let q = Vec::from(params.read_unsigned_integer()?);
+        let g = Vec::from(params.read_unsigned_integer()?);
+        let y = Vec::from(DerReader::new(public_key).read_unsigned_integer()?);
+
+        Ok(PublicKey::Dsa { p, q, g, y })
+      }
+      _ => Err(Error::CouldNotReadKey),
+    }
+  }
+
+  /// Parse a `-----BEGIN PUBLIC KEY-----` PEM block.
+  pub fn parse_pkix_pem(pem: &str) -> Result<PublicKey> {
+    let mut encoded = String::new();
+    let mut started = false;
+
+    for line in pem.lines().map(str::trim) {
+      if line == PEM_BEGIN {
+        started = true;
+      } else if line == PEM_END {
+        return Self::parse_pkix_der(&base64::decode_config(&encoded, base64::STANDARD)?);
+      } else if started {
+        encoded.push_str(line);
+      }
+    }
+
+    Err(Error::CouldNotReadKey)
+  }
+
+  /// Encode as DER X.509 SubjectPublicKeyInfo, like `ssh-keygen -e -m PKCS8`. Like `ssh-keygen`, security
+  /// keys are refused, as the SubjectPublicKeyInfo would turn them into plain keys.
+  pub fn to_pkix_der(&self) -> Result<Vec<u8>> {
+    let oid = |oid: &[u8]| mini_der::encode(mini_der::TAG_OID, oid);
+    let (algorithm, public_key) = match self {
+      PublicKey::Ed25519(key) => (mini_der::encode_sequence(&[&oid(OID_ED25519)]), key.clone()),
+      PublicKey::EcdsaP256(key) => (
+        mini_der::encode_sequence(&[&oid(OID_EC_PUBLIC_KEY), &oid(OID_PRIME256V1)]),
+        key.clone(),
+      ),
+      PublicKey::EcdsaP384(key) => (
+        mini_der::encode_sequence(&[&oid(OID_EC_PUBLIC_KEY), &oid(OID_SECP384R1)]),
+        key.clone(),
+      ),
+      PublicKey::EcdsaP521(key) => (
+        mini_der::encode_sequence(&[&oid(OID_EC_PUBLIC_KEY), &oid(OID_SECP521R1)]),
+        key.clone(),
+      ),
+      PublicKey::Rsa { e, n } => (
+        mini_der::encode_sequence(&[&oid(OID_RSA_ENCRYPTION), &mini_der::encode(mini_der::TAG_NULL, &[])]),
+        mini_der::encode_sequence(&[
+          &mini_der::encode_unsigned_integer(n),
+          &mini_der::encode_unsigned_integer(e),
+        ]),
+      ),
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { p, q, g, y } => (
+        mini_der::encode_sequence(&[
+          &oid(OID_DSA),
+          &mini_der::encode_sequence(&[
+            &mini_der::encode_unsigned_integer(p),
+            &mini_der::encode_unsigned_integer(q),
+            &mini_der::encode_unsigned_integer(g),
+          ]),
+        ]),
+        mini_der::encode_unsigned_integer(y),
+      ),
+      PublicKey::SkEcdsaP256 { .. }
+      | PublicKey::SkEd25519 { .. }
+      | PublicKey::Certificate(_)
+      | PublicKey::Unknown { .. } => return Err(Error::UnsupportedKeyType(self.algorithm().to_string())),
+    };
+
+    Ok(mini_der::encode_sequence(&[
+      &algorithm,
+      &mini_der::encode_bit_string(&public_key),
+    ]))
+  }
+
+  pub fn to_pkix_pem(&self) -> Result<String> {
+    let encoded = base64::encode_config(self.to_pkix_der()?, base64::STANDARD);
+    let mut pem = String::with_capacity(encoded.len() + 64);
+
+    pem.push_str(PEM_BEGIN);
+    pem.push('\n');
+    for chunk in encoded.as_bytes().chunks(PEM_LINE_LENGTH) {
+      // base64 output is plain ascii
+      pem.push_str(std::str::from_utf8(chunk).unwrap());
+      pem.push('\n');
+    }
+    pem.push_str(PEM_END);
+    pem.push('\n');
+
+    Ok(pem)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn matches_ssh_keygen_pkcs8() {
+    for name in [
+      "unencrypted_rsa",
+      "unencrypted_ecdsa",
+      "unencrypted_ecdsa384",
+      "unencrypted_ecdsa521",
+    ]
+    .iter()
+    {
+      let key = read_pub_key(&format!("{}.pub", name));
+      let pem = fs::read_to_string(format!("fixtures/{}.pkix.pem", name)).unwrap();
+
+      assert_eq!(key.to_pkix_pem().unwrap(), pem);
+      assert_eq!(PublicKey::parse_pkix_pem(&pem).unwrap().to_ssh_key(), key.to_ssh_key());
+    }
+  }
+
+  #[cfg(feature = "legacy-dsa")]
+  #[test]
+  fn dsa_matches_ssh_keygen_pkcs8() {
+    let key = read_pub_key("unencrypted_dsa.pub");
+    let pem = fs::read_to_string("fixtures/unencrypted_dsa.pkix.pem").unwrap();
+
+    assert_eq!(key.to_pkix_pem().unwrap(), pem);
+    assert_eq!(PublicKey::parse_pkix_pem(&pem).unwrap().to_ssh_key(), key.to_ssh_key());
+  }
+
+  #[test]
+  fn ed25519_roundtrip() {
+    let key = read_pub_key("unencrypted_ed25519.pub");
+    let der = key.to_pkix_der().unwrap();
+    let raw = match &key {
+      PublicKey::Ed25519(raw) => raw.clone(),
+      _ => panic!("Not an ed25519 key"),
+    };
+
+    // RFC 8410 fixed prefix for Ed25519 public keys
+    assert_eq!(
+      &der[..12],
+      &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00]
+    );
+    assert_eq!(&der[12..], raw.as_slice());
+    assert_eq!(PublicKey::parse_pkix_der(&der).unwrap().to_ssh_key(), key.to_ssh_key());
+  }
+
+  #[test]
+  fn reject_negative_integers() {
+    let negative_modulus = mini_der::encode_sequence(&[
+      &mini_der::encode_sequence(&[
+        &mini_der::encode(mini_der::TAG_OID, OID_RSA_ENCRYPTION),
+        &mini_der::encode(mini_der::TAG_NULL, &[]),
+      ]),
+      &mini_der::encode_bit_string(&mini_der::encode_sequence(&[
+        &mini_der::encode(0x02, &[0x80; 256]),
+        &mini_der::encode(0x02, &[0x01, 0x00, 0x01]),
+      ])),
+    ]);
+
+    assert!(PublicKey::parse_pkix_der(&negative_modulus).is_err());
+  }
+
+  #[test]
+  fn reject_trailing_data() {
+    let mut der = read_pub_key("unencrypted_ed25519.pub").to_pkix_der().unwrap();
+    der.push(0);
+
+    assert!(PublicKey::parse_pkix_der(&der).is_err());
+  }
+
+  #[test]
+  fn reject_unknown_key() {
+    let key = PublicKey::Unknown {
+      algorithm: "unknown@example.com".to_string(),
+      blob: vec![],
+    };
+
+    assert!(key.to_pkix_pem().is_err());
+  }
+
+  #[test]
+  fn reject_security_keys() {
+    for name in ["sk_ed25519.pub", "sk_ecdsa.pub"].iter() {
+      match read_pub_key(name).to_pkix_der() {
+        Err(Error::UnsupportedKeyType(_)) => (),
+        other => panic!("Unexpected result for {}: {:?}", name, other),
+      }
+    }
+  }
+}
@@ -0,0 +1,261 @@
+use crate::error::{Error, Result};
+#[cfg(feature = "legacy-dsa")]
+use crate::mini_dsa;
+use crate::mini_ec::Curve;
+use crate::public::{mpint_bits, PublicKey};
+
+/// Admission rules for public keys, see `PublicKey::validate_with`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPolicy {
+  /// Minimum size of an RSA modulus in bits.
+  pub min_rsa_bits: u32,
+  /// Minimum RSA public exponent. Exponents also have to be odd and below 2^33, the largest ring can verify.
+  pub min_rsa_exponent: u64,
+}
+
+impl Default for KeyPolicy {
+  fn default() -> Self {
+    KeyPolicy {
+      min_rsa_bits: 2048,
+      // OpenSSH used to generate keys with e = 35, so anything odd is accepted by default
+      min_rsa_exponent: 3,
+    }
+  }
+}
+
+// ring only verifies RSA moduli of up to 8192 bits
+const MAX_RSA_BITS: u32 = 8192;
+const MAX_RSA_EXPONENT_BITS: u32 = 33;
+
+impl PublicKey {
+  /// Check the key against the default `KeyPolicy`.
+  pub fn validate(&self) -> Result<()> {
+    self.validate_with(&KeyPolicy::default())
+  }
+
+  /// Check that the key is well formed and strong enough for `policy`, so that weak or broken keys can be
+  /// rejected when they are uploaded rather than when they are first used.
+  pub fn validate_with(&self, policy: &KeyPolicy) -> Result<()> {
+    match self {
+      // The length of Ed25519 keys is already checked when parsing
+      PublicKey::Ed25519(_) | PublicKey::SkEd25519 { .. } => Ok(()),
+      PublicKey::EcdsaP256(point) | PublicKey::SkEcdsaP256 { key: point, .. } => validate_point(&Curve::p256(), point),
+      PublicKey::EcdsaP384(point) => validate_point(&Curve::p384(), point),
+      PublicKey::EcdsaP521(point) => validate_point(&Curve::p521(), point),
+      PublicKey::Rsa { e, n } => {
+        let bits = mpint_bits(n);
+        if bits < policy.min_rsa_bits {
+          return Err(Error::KeyTooSmall(bits, policy.min_rsa_bits));
+        }
+        if bits > MAX_RSA_BITS {
+          return Err(Error::KeyTooLarge(bits, MAX_RSA_BITS));
+        }
+        if !matches!(n.last(), Some(b) if b & 1 == 1) {
+          return Err(Error::InvalidRsaModulus);
+        }
+        validate_exponent(e, policy)
+      }
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { p, q, .. } => {
+        // 1024 bit is the only size ever used for ssh-dss, and the only one OpenSSH accepts
+        let bits = mpint_bits(p);
+        if bits < mini_dsa::P_BITS {
+          return Err(Error::KeyTooSmall(bits, mini_dsa::P_BITS));
+        }
+        if bits > mini_dsa::P_BITS {
+          return Err(Error::KeyTooLarge(bits, mini_dsa::P_BITS));
+        }
+        if mpint_bits(q) != mini_dsa::Q_BITS {
+          return Err(Error::InvalidKeyLength);
+        }
+        Ok(())
+      }
+      PublicKey::Certificate(cert) => cert.key.validate_with(policy),
+      PublicKey::Unknown { algorithm, .. } => Err(Error::UnsupportedKeyType(algorithm.clone())),
+    }
+  }
+}
+
+fn validate_point(curve: &Curve, point: &[u8]) -> Result<()> {
+  match point.first() {
+    Some(0x02) | Some(0x03) if point.len() == 1 + curve.field_len => Err(Error::CompressedEcPoint),
+    _ if point.len() != 1 + 2 * curve.field_len => Err(Error::InvalidKeyLength),
+    _ => match curve.decode_point(point) {
+      Some(_) => Ok(()),
+      None => Err(Error::InvalidEcPoint),
+    },
+  }
+}
+
+fn validate_exponent(e: &[u8], policy: &KeyPolicy) -> Result<()> {
+  if mpint_bits(e) > MAX_RSA_EXPONENT_BITS || !matches!(e.last(), Some(b) if b & 1 == 1) {
+    return Err(Error::InvalidRsaExponent);
+  }
+  let exponent = e.iter().fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+  if exponent < policy.min_rsa_exponent {
+    return Err(Error::InvalidRsaExponent);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn read_pub_key(name: &str) -> PublicKey {
+    PublicKey::parse_pub(&fs::read_to_string(format!("fixtures/{}", name)).unwrap()).unwrap()
+  }
+
+  #[test]
+  fn fixtures_are_valid() {
+    for name in [
+      "unencrypted_rsa.pub",
+      "unencrypted_ecdsa.pub",
+      "unencrypted_ecdsa384.pub",
+      "unencrypted_ecdsa521.pub",
+      "unencrypted_ed25519.pub",
+      "sk_ecdsa.pub",
+      "sk_ed25519.pub",
+    ]
+    .iter()
+    {
+      read_pub_key(name).validate().unwrap();
+    }
+  }
+
+  #[test]
+  fn ec_points() {
+    let point = match read_pub_key("unencrypted_ecdsa384.pub") {
+      PublicKey::EcdsaP384(point) => point,
+      _ => panic!("Not an ecdsa key"),
+    };
+    let mut off_curve = point.clone();
+    off_curve[60] ^= 1;
+    let mut compressed = point[..49].to_vec();
+    compressed[0] = 0x02 | (point[96] & 1);
+
+    match PublicKey::EcdsaP384(off_curve).validate() {
+      Err(Error::InvalidEcPoint) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match PublicKey::EcdsaP384(compressed).validate() {
+      Err(Error::CompressedEcPoint) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match PublicKey::EcdsaP256(point).validate() {
+      Err(Error::InvalidKeyLength) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn rsa_size_and_exponent() {
+    let (e, n) = match read_pub_key("unencrypted_rsa.pub") {
+      PublicKey::Rsa { e, n } => (e, n),
+      _ => panic!("Not a rsa key"),
+    };
+    let strict = KeyPolicy {
+      min_rsa_bits: 3072,
+      min_rsa_exponent: 65537,
+    };
+
+    match (PublicKey::Rsa {
+      e: e.clone(),
+      n: n.clone(),
+    })
+    .validate_with(&strict)
+    {
+      Err(Error::KeyTooSmall(2048, 3072)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match (PublicKey::Rsa {
+      e: e.clone(),
+      n: n[..129].to_vec(),
+    })
+    .validate()
+    {
+      Err(Error::KeyTooSmall(1024, 2048)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    let mut large_n = vec![0xff; 1026];
+    large_n[0] = 0x00;
+    match (PublicKey::Rsa {
+      e: e.clone(),
+      n: large_n,
+    })
+    .validate()
+    {
+      Err(Error::KeyTooLarge(8200, 8192)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    for bad_exponent in [vec![], vec![1], vec![0x01, 0x00, 0x00], vec![0x02, 0, 0, 0, 1]].iter() {
+      match (PublicKey::Rsa {
+        e: bad_exponent.clone(),
+        n: n.clone(),
+      })
+      .validate()
+      {
+        Err(Error::InvalidRsaExponent) => (),
+        other => panic!("Unexpected result for {:?}: {:?}", bad_exponent, other),
+      }
+    }
+    let strict_exponent = KeyPolicy {
+      min_rsa_exponent: 65537,
+      ..KeyPolicy::default()
+    };
+    match (PublicKey::Rsa { e: vec![35], n }).validate_with(&strict_exponent) {
+      Err(Error::InvalidRsaExponent) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[cfg(feature = "legacy-dsa")]
+  #[test]
+  fn dsa_size() {
+    let key = read_pub_key("unencrypted_dsa.pub");
+    key.validate().unwrap();
+
+    let (p, q, g, y) = match key {
+      PublicKey::Dsa { p, q, g, y } => (p, q, g, y),
+      _ => panic!("Not a dsa key"),
+    };
+    let mut large_p = vec![0xff; 128];
+    large_p.extend_from_slice(&p);
+    match (PublicKey::Dsa {
+      p: large_p,
+      q: q.clone(),
+      g: g.clone(),
+      y: y.clone(),
+    })
+    .validate()
+    {
+      Err(Error::KeyTooLarge(_, 1024)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match (PublicKey::Dsa {
+      p,
+      q: q[..q.len() - 1].to_vec(),
+      g,
+      y,
+    })
+    .validate()
+    {
+      Err(Error::InvalidKeyLength) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn unknown_keys_are_rejected() {
+    let key = PublicKey::Unknown {
+      algorithm: "unknown@example.com".to_string(),
+      blob: vec![],
+    };
+
+    match key.validate() {
+      Err(Error::UnsupportedKeyType(_)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+}
@@ -1,5 +1,8 @@
 use crate::error::{Error, Result};
+use crate::public::PublicKey;
+use crate::signature::{Signature, SignatureHash};
 use hex::FromHex;
+use ring::signature::Ed25519KeyPair;
 mod bcrypt_pbkdf;
 mod blowflish;
 mod openssh;
@@ -12,6 +15,33 @@ pub enum KeyPair {
   Ed25519 { pubkey: Vec<u8>, seckey: Vec<u8> },
 }
 
+impl KeyPair {
+  pub fn public_key(&self) -> Result<PublicKey> {
+    match self {
+      KeyPair::Ed25519 { pubkey, .. } => Ok(PublicKey::Ed25519(pubkey.clone())),
+      KeyPair::Rsa => Err(Error::UnsupportedKeyType("ssh-rsa".to_string())),
+    }
+  }
+
+  /// Only Ed25519 keys can sign, RSA keys are not decoded yet.
+  pub fn sign(&self, data: &[u8]) -> Result<Signature> {
+    match self {
+      KeyPair::Ed25519 { pubkey, seckey } => {
+        // OpenSSH stores the seed followed by the public key
+        let seed = seckey.get(..32).ok_or(Error::InvalidKeyLength)?;
+        let key_pair = Ed25519KeyPair::from_seed_and_public_key(seed, pubkey).map_err(|_| Error::CouldNotReadKey)?;
+
+        Ok(Signature {
+          hash: SignatureHash::Ed25519,
+          signature: key_pair.sign(data).as_ref().to_vec(),
+          security_key: None,
+        })
+      }
+      KeyPair::Rsa => Err(Error::UnsupportedKeyType("ssh-rsa".to_string())),
+    }
+  }
+}
+
 #[derive(Clone, Copy, Debug)]
 /// AES encryption key.
 pub enum Encryption {
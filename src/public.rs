@@ -1,61 +1,229 @@
+use crate::certificate::Certificate;
 use crate::encoding::{Reader, Writer};
 use crate::error::{Error, Result};
+use crate::key_type::KeyType;
+use crate::md5;
 use crate::mini_der;
 use crate::signature::SignatureHash;
+use ring::digest;
 use ring::signature::ED25519_PUBLIC_KEY_LEN;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
 pub const SSH_ED25519: &[u8] = b"ssh-ed25519";
 pub const SSH_ECDSA_P256: &[u8] = b"ecdsa-sha2-nistp256";
 pub const SSH_ECDSA_P384: &[u8] = b"ecdsa-sha2-nistp384";
+pub const SSH_ECDSA_P521: &[u8] = b"ecdsa-sha2-nistp521";
 pub const SSH_ECDSA_P256_KEY_TYPE: &[u8] = b"nistp256";
 pub const SSH_ECDSA_P384_KEY_TYPE: &[u8] = b"nistp384";
+pub const SSH_ECDSA_P521_KEY_TYPE: &[u8] = b"nistp521";
+pub const SSH_SK_ECDSA_P256: &[u8] = b"sk-ecdsa-sha2-nistp256@openssh.com";
+pub const SSH_SK_ED25519: &[u8] = b"sk-ssh-ed25519@openssh.com";
 pub const SSH_RSA: &[u8] = b"ssh-rsa";
+pub const SSH_DSS: &[u8] = b"ssh-dss";
 pub const SSH_RSA_SHA2_256: &[u8] = b"rsa-sha2-256";
 pub const SSH_RSA_SHA2_512: &[u8] = b"rsa-sha2-512";
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintHash {
+  Sha256,
+  Md5,
+}
+
+impl FingerprintHash {
+  pub fn name(&self) -> &'static str {
+    match self {
+      FingerprintHash::Sha256 => "SHA256",
+      FingerprintHash::Md5 => "MD5",
+    }
+  }
+}
+
+/// Equality, hashing and ordering are all based on the wire blob, see `to_ssh_key`.
+#[derive(Debug, Clone)]
 pub enum PublicKey {
   Ed25519(Vec<u8>),
   EcdsaP256(Vec<u8>),
   EcdsaP384(Vec<u8>),
+  EcdsaP521(Vec<u8>),
+  /// FIDO security key backed ECDSA key, bound to an application (usually `ssh:`).
+  SkEcdsaP256 {
+    key: Vec<u8>,
+    application: String,
+  },
+  /// FIDO security key backed Ed25519 key, bound to an application (usually `ssh:`).
+  SkEd25519 {
+    key: Vec<u8>,
+    application: String,
+  },
   Rsa {
     e: Vec<u8>,
     n: Vec<u8>,
-    preferred_hash: SignatureHash,
+  },
+  /// Legacy DSA key, only for auditing old hosts.
+  #[cfg(feature = "legacy-dsa")]
+  Dsa {
+    p: Vec<u8>,
+    q: Vec<u8>,
+    g: Vec<u8>,
+    y: Vec<u8>,
+  },
+  /// OpenSSH certificate, i.e. a key signed by a certificate authority.
+  Certificate(Box<Certificate>),
+  /// Key of a type this crate does not understand, kept as the raw wire blob.
+  Unknown {
+    algorithm: String,
+    blob: Vec<u8>,
   },
 }
 
-impl PublicKey {
-  pub fn parse_pub(line: &str) -> Result<PublicKey> {
-    let parts: Vec<&str> = line.split(' ').collect();
+impl PartialEq for PublicKey {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_ssh_key() == other.to_ssh_key()
+  }
+}
 
-    if parts.len() < 2 {
-      return Err(Error::CouldNotReadKey);
-    }
-    let raw_key = base64::decode_config(parts[1], base64::STANDARD)?;
-    let key = Self::parse_raw(&raw_key)?;
+impl Eq for PublicKey {}
 
-    match (parts[0].as_bytes(), key) {
-      (SSH_ED25519, k @ PublicKey::Ed25519(_)) => Ok(k),
-      (SSH_ECDSA_P256, k @ PublicKey::EcdsaP256(_)) => Ok(k),
-      (SSH_ECDSA_P384, k @ PublicKey::EcdsaP384(_)) => Ok(k),
-      (SSH_RSA, PublicKey::Rsa { e, n, .. }) => Ok(PublicKey::Rsa {
-        n,
-        e,
-        preferred_hash: SignatureHash::RsaSha1,
-      }),
-      (SSH_RSA_SHA2_256, PublicKey::Rsa { e, n, .. }) => Ok(PublicKey::Rsa {
-        n,
-        e,
-        preferred_hash: SignatureHash::RsaSha256,
-      }),
-      (SSH_RSA_SHA2_512, PublicKey::Rsa { e, n, .. }) => Ok(PublicKey::Rsa {
-        n,
-        e,
-        preferred_hash: SignatureHash::RsaSha512,
-      }),
-      _ => Err(Error::CouldNotReadKey),
+impl Hash for PublicKey {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.to_ssh_key().hash(state)
+  }
+}
+
+impl PartialOrd for PublicKey {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for PublicKey {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.to_ssh_key().cmp(&other.to_ssh_key())
+  }
+}
+
+/// OpenSSH single line format without comment.
+impl fmt::Display for PublicKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_openssh_line(None))
+  }
+}
+
+/// Like `parse_pub`, but keys of a type this crate does not know are kept as `PublicKey::Unknown`, so that
+/// everything `Display` writes can be read back.
+impl FromStr for PublicKey {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<PublicKey> {
+    Ok(parse_line(s, PublicKey::parse_raw_or_unknown)?.key)
+  }
+}
+
+/// A public key as found in a single line of an OpenSSH `.pub` or `authorized_keys` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyLine {
+  pub algorithm: String,
+  pub key: PublicKey,
+  pub comment: Option<String>,
+}
+
+impl PublicKeyLine {
+  pub fn parse(line: &str) -> Result<PublicKeyLine> {
+    parse_line(line, PublicKey::parse_raw)
+  }
+
+  /// Like `parse`, but keys of a type this crate does not know are kept as `PublicKey::Unknown`.
+  pub fn parse_or_unknown(line: &str) -> Result<PublicKeyLine> {
+    parse_line(line, PublicKey::parse_raw_or_unknown)
+  }
+
+  pub fn to_openssh_line(&self) -> String {
+    format_line(&self.algorithm, &self.key, self.comment.as_deref())
+  }
+}
+
+impl fmt::Display for PublicKeyLine {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_openssh_line())
+  }
+}
+
+fn parse_line(line: &str, parse_raw: fn(&[u8]) -> Result<PublicKey>) -> Result<PublicKeyLine> {
+  let line = line.trim();
+  let (algorithm, rest) = split_field(line).ok_or(Error::CouldNotReadKey)?;
+  let (encoded, comment) = split_field(rest).ok_or(Error::CouldNotReadKey)?;
+  let raw_key = base64::decode_config(encoded, base64::STANDARD)?;
+  let key = parse_raw(&raw_key)?;
+
+  let key = match (algorithm.as_bytes(), key) {
+    (SSH_ED25519, k @ PublicKey::Ed25519(_)) => k,
+    (SSH_ECDSA_P256, k @ PublicKey::EcdsaP256(_)) => k,
+    (SSH_ECDSA_P384, k @ PublicKey::EcdsaP384(_)) => k,
+    (SSH_ECDSA_P521, k @ PublicKey::EcdsaP521(_)) => k,
+    (SSH_SK_ECDSA_P256, k @ PublicKey::SkEcdsaP256 { .. }) => k,
+    (SSH_SK_ED25519, k @ PublicKey::SkEd25519 { .. }) => k,
+    (SSH_RSA, k @ PublicKey::Rsa { .. }) => k,
+    (SSH_RSA_SHA2_256, k @ PublicKey::Rsa { .. }) => k,
+    (SSH_RSA_SHA2_512, k @ PublicKey::Rsa { .. }) => k,
+    #[cfg(feature = "legacy-dsa")]
+    (SSH_DSS, k @ PublicKey::Dsa { .. }) => k,
+    (name, k @ PublicKey::Certificate(_)) | (name, k @ PublicKey::Unknown { .. })
+      if name == k.algorithm().as_bytes() =>
+    {
+      k
     }
+    _ => return Err(Error::CouldNotReadKey),
+  };
+  let comment = if comment.is_empty() {
+    None
+  } else {
+    Some(comment.to_string())
+  };
+
+  Ok(PublicKeyLine {
+    algorithm: algorithm.to_string(),
+    key,
+    comment,
+  })
+}
+
+fn format_line(algorithm: &str, key: &PublicKey, comment: Option<&str>) -> String {
+  let encoded = base64::encode_config(key.to_ssh_key(), base64::STANDARD);
+
+  match comment {
+    Some(comment) => format!("{} {} {}", algorithm, encoded, comment),
+    None => format!("{} {}", algorithm, encoded),
+  }
+}
+
+pub(crate) fn mpint_bits(value: &[u8]) -> u32 {
+  match value.iter().position(|b| *b != 0) {
+    Some(i) => (value.len() - i) as u32 * 8 - value[i].leading_zeros(),
+    None => 0,
+  }
+}
+
+/// Split off the first whitespace separated field, returning it and the (left trimmed) remainder.
+fn split_field(s: &str) -> Option<(&str, &str)> {
+  if s.is_empty() {
+    return None;
+  }
+  match s.find([' ', '\t']) {
+    Some(idx) => Some((&s[..idx], s[idx..].trim_start())),
+    None => Some((s, "")),
+  }
+}
+
+impl PublicKey {
+  pub fn parse_pub(line: &str) -> Result<PublicKey> {
+    Ok(PublicKeyLine::parse(line)?.key)
+  }
+
+  pub fn to_openssh_line(&self, comment: Option<&str>) -> String {
+    format_line(self.algorithm(), self, comment)
   }
 
   pub fn parse_raw(raw_key: &[u8]) -> Result<PublicKey> {
@@ -63,6 +231,14 @@ impl PublicKey {
 
     let algo = reader.read_string()?;
 
+    if Certificate::is_certificate_algorithm(algo) {
+      return Ok(PublicKey::Certificate(Box::new(Certificate::parse_raw(raw_key)?)));
+    }
+    Self::read_key_body(algo, &mut reader)
+  }
+
+  /// Read the key fields following the algorithm name, shared with the key embedded in certificates.
+  pub(crate) fn read_key_body(algo: &[u8], reader: &mut Reader) -> Result<PublicKey> {
     match algo {
       SSH_ED25519 => {
         let key = reader.read_string()?;
@@ -93,44 +269,134 @@ impl PublicKey {
           Err(Error::CouldNotReadKey)
         }
       }
+      SSH_ECDSA_P521 => {
+        let key_type = reader.read_string()?;
+        let q = reader.read_string()?;
+
+        if key_type == SSH_ECDSA_P521_KEY_TYPE {
+          Ok(PublicKey::EcdsaP521(Vec::from(q)))
+        } else {
+          Err(Error::CouldNotReadKey)
+        }
+      }
+      SSH_SK_ECDSA_P256 => {
+        let key_type = reader.read_string()?;
+        let q = reader.read_string()?;
+        let application = std::str::from_utf8(reader.read_string()?)?.to_string();
+
+        if key_type == SSH_ECDSA_P256_KEY_TYPE {
+          Ok(PublicKey::SkEcdsaP256 {
+            key: Vec::from(q),
+            application,
+          })
+        } else {
+          Err(Error::CouldNotReadKey)
+        }
+      }
+      SSH_SK_ED25519 => {
+        let key = reader.read_string()?;
+        let application = std::str::from_utf8(reader.read_string()?)?.to_string();
+
+        if key.len() == ED25519_PUBLIC_KEY_LEN {
+          Ok(PublicKey::SkEd25519 {
+            key: Vec::from(key),
+            application,
+          })
+        } else {
+          Err(Error::InvalidKeyLength)
+        }
+      }
       SSH_RSA => {
         let e = Vec::from(reader.read_string()?);
         let n = Vec::from(reader.read_string()?);
 
-        Ok(PublicKey::Rsa {
-          n,
-          e,
-          preferred_hash: SignatureHash::RsaSha512, // By default we always prefer SHA2 signatures
-        })
+        Ok(PublicKey::Rsa { n, e })
+      }
+      #[cfg(feature = "legacy-dsa")]
+      SSH_DSS => {
+        let p = Vec::from(reader.read_string()?);
+        let q = Vec::from(reader.read_string()?);
+        let g = Vec::from(reader.read_string()?);
+        let y = Vec::from(reader.read_string()?);
+
+        Ok(PublicKey::Dsa { p, q, g, y })
       }
       _ => Err(Error::CouldNotReadKey),
     }
   }
 
+  /// Like `parse_raw`, but keys of a type this crate does not know are kept as `PublicKey::Unknown`.
+  pub fn parse_raw_or_unknown(raw_key: &[u8]) -> Result<PublicKey> {
+    let algorithm = Reader::new(raw_key).read_string()?;
+
+    match algorithm {
+      SSH_ED25519 | SSH_ECDSA_P256 | SSH_ECDSA_P384 | SSH_ECDSA_P521 | SSH_SK_ECDSA_P256 | SSH_SK_ED25519 | SSH_RSA => {
+        Self::parse_raw(raw_key)
+      }
+      #[cfg(feature = "legacy-dsa")]
+      SSH_DSS => Self::parse_raw(raw_key),
+      _ if Certificate::is_certificate_algorithm(algorithm) => Self::parse_raw(raw_key),
+      _ => Ok(PublicKey::Unknown {
+        algorithm: String::from_utf8_lossy(algorithm).into_owned(),
+        blob: Vec::from(raw_key),
+      }),
+    }
+  }
+
   pub fn to_ssh_key(&self) -> Vec<u8> {
+    if let PublicKey::Unknown { blob, .. } = self {
+      return blob.clone();
+    }
     let mut writer = Writer::new();
+    writer.write_string(self.algorithm().as_bytes());
+    self.write_key_body(&mut writer);
+    writer.buffer
+  }
+
+  /// Write everything following the algorithm name, see `read_key_body`.
+  pub(crate) fn write_key_body(&self, writer: &mut Writer) {
     match self {
-      PublicKey::Ed25519(key) => {
-        writer.write_string(SSH_ED25519);
-        writer.write_string(key);
-      }
+      PublicKey::Ed25519(key) => writer.write_string(key),
       PublicKey::EcdsaP256(key) => {
-        writer.write_string(SSH_ECDSA_P256);
         writer.write_string(SSH_ECDSA_P256_KEY_TYPE);
         writer.write_string(key);
       }
       PublicKey::EcdsaP384(key) => {
-        writer.write_string(SSH_ECDSA_P384);
         writer.write_string(SSH_ECDSA_P384_KEY_TYPE);
         writer.write_string(key);
       }
-      PublicKey::Rsa { e, n, .. } => {
-        writer.write_string(SSH_RSA);
+      PublicKey::EcdsaP521(key) => {
+        writer.write_string(SSH_ECDSA_P521_KEY_TYPE);
+        writer.write_string(key);
+      }
+      PublicKey::SkEcdsaP256 { key, application } => {
+        writer.write_string(SSH_ECDSA_P256_KEY_TYPE);
+        writer.write_string(key);
+        writer.write_string(application.as_bytes());
+      }
+      PublicKey::SkEd25519 { key, application } => {
+        writer.write_string(key);
+        writer.write_string(application.as_bytes());
+      }
+      PublicKey::Rsa { e, n } => {
         writer.write_string(e);
         writer.write_string(n);
       }
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { p, q, g, y } => {
+        writer.write_string(p);
+        writer.write_string(q);
+        writer.write_string(g);
+        writer.write_string(y);
+      }
+      PublicKey::Certificate(cert) => cert.write_body(writer),
+      PublicKey::Unknown { blob, .. } => {
+        let mut reader = Reader::new(blob);
+        // The blob of an unknown key always starts with its algorithm name, see `parse_raw_or_unknown`
+        let _ = reader.read_string();
+        writer.buffer.extend_from_slice(reader.remaining());
+      }
     }
-    writer.buffer
   }
 
   pub fn to_ring_key(&self) -> Vec<u8> {
@@ -138,14 +404,127 @@ impl PublicKey {
       PublicKey::Ed25519(key) => key.clone(),
       PublicKey::EcdsaP256(key) => key.clone(),
       PublicKey::EcdsaP384(key) => key.clone(),
-      PublicKey::Rsa { n, e, .. } => mini_der::encode_rsa_public(n, e),
+      PublicKey::EcdsaP521(key) => key.clone(),
+      PublicKey::SkEcdsaP256 { key, .. } => key.clone(),
+      PublicKey::SkEd25519 { key, .. } => key.clone(),
+      PublicKey::Rsa { n, e } => mini_der::encode_rsa_public(n, e),
+      // ring does not know DSA, see mini_dsa
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { y, .. } => y.clone(),
+      PublicKey::Certificate(cert) => cert.key.to_ring_key(),
+      PublicKey::Unknown { blob, .. } => blob.clone(),
+    }
+  }
+
+  /// Wire name of the key type, e.g. `ssh-ed25519`.
+  pub fn algorithm(&self) -> &str {
+    match self {
+      PublicKey::Ed25519(_) => KeyType::Ed25519.name(),
+      PublicKey::EcdsaP256(_) => KeyType::EcdsaP256.name(),
+      PublicKey::EcdsaP384(_) => KeyType::EcdsaP384.name(),
+      PublicKey::EcdsaP521(_) => KeyType::EcdsaP521.name(),
+      PublicKey::SkEcdsaP256 { .. } => KeyType::SkEcdsaP256.name(),
+      PublicKey::SkEd25519 { .. } => KeyType::SkEd25519.name(),
+      PublicKey::Rsa { .. } => KeyType::Rsa.name(),
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { .. } => KeyType::Dsa.name(),
+      PublicKey::Certificate(cert) => cert.algorithm(),
+      PublicKey::Unknown { algorithm, .. } => algorithm,
+    }
+  }
+
+  /// Key size in bits: the exact modulus size for RSA, the curve size for EC and 256 for Ed25519.
+  pub fn bits(&self) -> u32 {
+    match self {
+      PublicKey::Ed25519(_) => 256,
+      PublicKey::EcdsaP256(_) => 256,
+      PublicKey::EcdsaP384(_) => 384,
+      PublicKey::EcdsaP521(_) => 521,
+      PublicKey::SkEcdsaP256 { .. } | PublicKey::SkEd25519 { .. } => 256,
+      PublicKey::Rsa { n, .. } => mpint_bits(n),
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { p, .. } => mpint_bits(p),
+      PublicKey::Certificate(cert) => cert.key.bits(),
+      PublicKey::Unknown { .. } => 0,
+    }
+  }
+
+  /// Short key type name as shown by `ssh-keygen -l`.
+  pub(crate) fn type_label(&self) -> &'static str {
+    match (self, self.key_type()) {
+      (PublicKey::Certificate(_), Some(key_type)) => key_type.certificate_label(),
+      (_, Some(key_type)) => key_type.label(),
+      (_, None) => "UNKNOWN",
+    }
+  }
+
+  /// Signature algorithms usable with this key, most preferred first.
+  pub fn signature_hashes(&self) -> &'static [SignatureHash] {
+    match self {
+      PublicKey::Ed25519(_) => &[SignatureHash::Ed25519],
+      PublicKey::EcdsaP256(_) => &[SignatureHash::EcdsaP256],
+      PublicKey::EcdsaP384(_) => &[SignatureHash::EcdsaP384],
+      PublicKey::EcdsaP521(_) => &[SignatureHash::EcdsaP521],
+      PublicKey::SkEcdsaP256 { .. } => &[SignatureHash::SkEcdsaP256],
+      PublicKey::SkEd25519 { .. } => &[SignatureHash::SkEd25519],
+      PublicKey::Rsa { .. } => &[
+        SignatureHash::RsaSha512,
+        SignatureHash::RsaSha256,
+        SignatureHash::RsaSha1,
+      ],
+      #[cfg(feature = "legacy-dsa")]
+      PublicKey::Dsa { .. } => &[SignatureHash::Dss],
+      PublicKey::Certificate(cert) => cert.key.signature_hashes(),
+      PublicKey::Unknown { .. } => &[],
     }
   }
+
+  /// The most preferred signature algorithm for this key that is contained in `allowed`.
+  pub fn best_signature_hash(&self, allowed: &[SignatureHash]) -> Option<SignatureHash> {
+    self
+      .signature_hashes()
+      .iter()
+      .find(|hash| allowed.contains(hash))
+      .copied()
+  }
+
+  /// Raw digest of the wire blob as used for fingerprints. Certificates use the blob of the certified key,
+  /// like OpenSSH does.
+  pub fn fingerprint_raw(&self, hash: FingerprintHash) -> Vec<u8> {
+    let blob = match self {
+      PublicKey::Certificate(cert) => cert.key.to_ssh_key(),
+      _ => self.to_ssh_key(),
+    };
+    match hash {
+      FingerprintHash::Sha256 => digest::digest(&digest::SHA256, &blob).as_ref().to_vec(),
+      FingerprintHash::Md5 => md5::digest(&blob).to_vec(),
+    }
+  }
+
+  /// Fingerprint as printed by `ssh-keygen -l -E <hash>`.
+  pub fn fingerprint(&self, hash: FingerprintHash) -> String {
+    let raw = self.fingerprint_raw(hash);
+    let encoded = match hash {
+      FingerprintHash::Sha256 => base64::encode_config(&raw, base64::STANDARD_NO_PAD),
+      FingerprintHash::Md5 => raw.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"),
+    };
+
+    format!("{}:{}", hash.name(), encoded)
+  }
+
+  pub fn fingerprint_sha256(&self) -> String {
+    self.fingerprint(FingerprintHash::Sha256)
+  }
+
+  pub fn fingerprint_md5(&self) -> String {
+    self.fingerprint(FingerprintHash::Md5)
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use std::collections::HashSet;
   use std::fs::File;
   use std::io::{BufRead, BufReader};
 
@@ -164,6 +543,29 @@ mod tests {
     }
   }
 
+  #[test]
+  fn rsa_key_identity() {
+    let line = read_first_line("fixtures/unencrypted_rsa.pub");
+    let sha2_line = line.replacen("ssh-rsa", "rsa-sha2-256", 1);
+    let key = PublicKey::parse_pub(&line).unwrap();
+    let sha2_key = PublicKey::parse_pub(&sha2_line).unwrap();
+
+    assert_eq!(key, sha2_key);
+    assert_eq!(
+      vec![sha2_key.clone(), key.clone()]
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .len(),
+      1
+    );
+    assert_eq!(PublicKeyLine::parse(&sha2_line).unwrap().to_openssh_line(), sha2_line);
+    assert_eq!(
+      key.best_signature_hash(&[SignatureHash::RsaSha1, SignatureHash::RsaSha512]),
+      Some(SignatureHash::RsaSha512)
+    );
+    assert_eq!(key.best_signature_hash(&[SignatureHash::Ed25519]), None);
+  }
+
   #[test]
   fn read_ecdsa_pub() {
     let line = read_first_line("fixtures/unencrypted_ecdsa.pub");
@@ -182,6 +584,73 @@ mod tests {
     }
   }
 
+  #[test]
+  fn read_ecdsa521_pub() {
+    let line = read_first_line("fixtures/unencrypted_ecdsa521.pub");
+    let key = PublicKey::parse_pub(&line).unwrap();
+    match key {
+      PublicKey::EcdsaP521(ref q) => assert_eq!(q.len(), 133),
+      _ => panic!("Not an ecdsa key"),
+    }
+    assert_eq!(key.to_openssh_line(Some("test@ssh-key-sign")), line);
+  }
+
+  #[test]
+  fn read_sk_keys() {
+    let line = read_first_line("fixtures/sk_ecdsa.pub");
+    match PublicKey::parse_pub(&line).unwrap() {
+      PublicKey::SkEcdsaP256 { ref application, .. } => assert_eq!(application, "ssh:"),
+      _ => panic!("Not an sk-ecdsa key"),
+    }
+    let line = read_first_line("fixtures/sk_ed25519.pub");
+    let key = PublicKey::parse_pub(&line).unwrap();
+    match key {
+      PublicKey::SkEd25519 { ref application, .. } => assert_eq!(application, "ssh:"),
+      _ => panic!("Not an sk-ed25519 key"),
+    }
+    assert_eq!(key.to_openssh_line(Some("test@ssh-key-sign")), line);
+    assert_eq!(
+      key.fingerprint_sha256(),
+      "SHA256:0a0Yq/kAbCerATpPgCj0pBs5nm7yisTSJtfD7SBZV9k"
+    );
+  }
+
+  #[cfg(feature = "legacy-dsa")]
+  #[test]
+  fn read_dsa_pub() {
+    let line = read_first_line("fixtures/unencrypted_dsa.pub");
+    let key = PublicKey::parse_pub(&line).unwrap();
+    match key {
+      PublicKey::Dsa { ref q, .. } => assert_eq!(mpint_bits(q), 160),
+      _ => panic!("Not a dsa key"),
+    }
+    assert_eq!(key.bits(), 1024);
+    assert_eq!(key.to_openssh_line(Some("test@ssh-key-sign")), line);
+    assert_eq!(
+      key.fingerprint_sha256(),
+      "SHA256:zPXTXcY3UaJU5PvNc2e2MiyVHiKJVUCDIeS2JwN9U1o"
+    );
+  }
+
+  #[cfg(not(feature = "legacy-dsa"))]
+  #[test]
+  fn dsa_is_unknown() {
+    let line = read_first_line("fixtures/unencrypted_dsa.pub");
+    let raw = base64::decode(line.split(' ').nth(1).unwrap()).unwrap();
+
+    assert!(PublicKey::parse_pub(&line).is_err());
+    let key = PublicKey::parse_raw_or_unknown(&raw).unwrap();
+    match key {
+      PublicKey::Unknown { ref algorithm, .. } => assert_eq!(algorithm, "ssh-dss"),
+      _ => panic!("Not an unknown key"),
+    }
+
+    // Unknown keys still round trip through Display and FromStr
+    assert_eq!(line.parse::<PublicKey>().unwrap(), key);
+    assert_eq!(key.to_string().parse::<PublicKey>().unwrap(), key);
+    assert!(line.replacen("ssh-dss", "ssh-foo", 1).parse::<PublicKey>().is_err());
+  }
+
   #[test]
   fn read_ed25519() {
     let line = read_first_line("fixtures/unencrypted_ed25519.pub");
@@ -190,4 +659,72 @@ mod tests {
       _ => panic!("Not an ed25519 key"),
     }
   }
+
+  #[test]
+  fn fingerprints() {
+    let fixtures = [
+      (
+        "fixtures/unencrypted_rsa.pub",
+        "SHA256:xrEf4vyoZTjSFJCXAMsvRNmVNJZNEof6B+U44uZC4Jk",
+        "MD5:45:a9:0e:4f:1f:a3:6b:08:1d:66:8b:27:aa:55:93:70",
+      ),
+      (
+        "fixtures/unencrypted_ecdsa.pub",
+        "SHA256:J71xYBoulM5Q/71sREa4s5+a0I+wjalgTYLCx7SPS6Q",
+        "MD5:9b:5d:7d:ab:e7:06:d0:57:e6:2b:d5:07:73:eb:c7:29",
+      ),
+      (
+        "fixtures/unencrypted_ecdsa384.pub",
+        "SHA256:MDwHjw4ZJz5KRJoolW6y1ed5kw7/8X2DMwhp3OzFqzc",
+        "MD5:d3:eb:9d:f2:c3:77:a2:02:2e:d6:39:5b:ff:a1:28:03",
+      ),
+      (
+        "fixtures/unencrypted_ed25519.pub",
+        "SHA256:FllisSv0s10o4DQyEg/qD9pNjQg9od+loyODZrFiDZ8",
+        "MD5:d7:c5:f7:81:6a:bb:f7:d0:54:62:f6:d9:11:38:c2:ce",
+      ),
+    ];
+
+    for (file, sha256, md5) in fixtures.iter() {
+      let key = PublicKey::parse_pub(&read_first_line(file)).unwrap();
+
+      assert_eq!(&key.fingerprint_sha256(), sha256);
+      assert_eq!(&key.fingerprint_md5(), md5);
+    }
+  }
+
+  #[test]
+  fn from_str() {
+    let line = read_first_line("fixtures/unencrypted_ed25519.pub");
+    let key: PublicKey = line.parse().unwrap();
+
+    assert_eq!(key, PublicKey::parse_pub(&line).unwrap());
+    assert_eq!(key.to_string().parse::<PublicKey>().unwrap(), key);
+    assert!(line.starts_with(&key.to_string()));
+    assert!("ssh-ed25519".parse::<PublicKey>().is_err());
+  }
+
+  #[test]
+  fn openssh_line_roundtrip() {
+    let line = read_first_line("fixtures/unencrypted_ed25519.pub");
+    let parsed = PublicKeyLine::parse(&line).unwrap();
+
+    assert_eq!(parsed.algorithm, "ssh-ed25519");
+    assert_eq!(parsed.comment.as_deref(), Some("bjunglas@archlinux"));
+    assert_eq!(parsed.to_openssh_line(), line);
+    assert_eq!(format!("{}", parsed), line);
+    assert_eq!(parsed.key.to_openssh_line(Some("bjunglas@archlinux")), line);
+
+    let with_spaces = line.replace("bjunglas@archlinux", "deploy key  for\thost a");
+    let parsed = PublicKeyLine::parse(&format!("{}\n", with_spaces)).unwrap();
+
+    assert_eq!(parsed.comment.as_deref(), Some("deploy key  for\thost a"));
+    assert_eq!(parsed.to_openssh_line(), with_spaces);
+
+    let parts: Vec<&str> = line.split(' ').collect();
+    let parsed = PublicKeyLine::parse(&format!("{}\t{}", parts[0], parts[1])).unwrap();
+
+    assert_eq!(parsed.comment, None);
+    assert_eq!(parsed.to_openssh_line(), format!("{} {}", parts[0], parts[1]));
+  }
 }
@@ -0,0 +1,130 @@
+use crate::public::{FingerprintHash, PublicKey};
+
+// Drunken bishop "visual host key", see `fingerprint_randomart` in OpenSSH's sshkey.c.
+const FIELD_BASE: usize = 8;
+const FIELD_Y: usize = FIELD_BASE + 1;
+const FIELD_X: usize = FIELD_BASE * 2 + 1;
+const AUGMENTATION: &[u8] = b" .o+=*BOX@%&#/^SE";
+
+impl PublicKey {
+  /// Randomart box as printed by `ssh-keygen -lv -E <hash>`.
+  pub fn randomart(&self, hash: FingerprintHash) -> String {
+    let title = {
+      let title = format!("[{} {}]", self.type_label(), self.bits());
+      if title.len() > FIELD_X {
+        format!("[{}]", self.type_label())
+      } else {
+        title
+      }
+    };
+    render(&self.fingerprint_raw(hash), &title, &format!("[{}]", hash.name()))
+  }
+}
+
+fn render(digest: &[u8], title: &str, footer: &str) -> String {
+  let len = AUGMENTATION.len() - 1;
+  let mut field = [[0usize; FIELD_Y]; FIELD_X];
+  let mut x = FIELD_X / 2;
+  let mut y = FIELD_Y / 2;
+
+  for byte in digest {
+    let mut input = *byte;
+    for _ in 0..4 {
+      x = if input & 0x1 != 0 {
+        (x + 1).min(FIELD_X - 1)
+      } else {
+        x.saturating_sub(1)
+      };
+      y = if input & 0x2 != 0 {
+        (y + 1).min(FIELD_Y - 1)
+      } else {
+        y.saturating_sub(1)
+      };
+
+      if field[x][y] < len - 2 {
+        field[x][y] += 1;
+      }
+      input >>= 2;
+    }
+  }
+
+  field[FIELD_X / 2][FIELD_Y / 2] = len - 1;
+  field[x][y] = len;
+
+  let mut result = String::with_capacity((FIELD_X + 3) * (FIELD_Y + 2));
+
+  border(&mut result, title);
+  result.push('\n');
+  for y in 0..FIELD_Y {
+    result.push('|');
+    for column in field.iter() {
+      result.push(AUGMENTATION[column[y].min(len)] as char);
+    }
+    result.push_str("|\n");
+  }
+  border(&mut result, footer);
+
+  result
+}
+
+fn border(target: &mut String, label: &str) {
+  // OpenSSH formats the labels into a buffer of FIELD_X bytes, so anything longer is truncated
+  let label = &label[..label.len().min(FIELD_X - 1)];
+  let left = (FIELD_X - label.len()) / 2;
+
+  target.push('+');
+  target.push_str(&"-".repeat(left));
+  target.push_str(label);
+  target.push_str(&"-".repeat(FIELD_X - left - label.len()));
+  target.push('+');
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  fn check_randomart(name: &str, hash: FingerprintHash, expected_file: &str) {
+    let line = fs::read_to_string(format!("fixtures/{}", name)).unwrap();
+    let key = PublicKey::parse_pub(line.trim_end()).unwrap();
+    let expected = fs::read_to_string(format!("fixtures/{}", expected_file)).unwrap();
+
+    assert_eq!(key.randomart(hash), expected.trim_end());
+  }
+
+  #[test]
+  fn randomart_matches_ssh_keygen() {
+    check_randomart(
+      "unencrypted_rsa.pub",
+      FingerprintHash::Sha256,
+      "unencrypted_rsa.pub.randomart",
+    );
+    check_randomart(
+      "unencrypted_ecdsa.pub",
+      FingerprintHash::Sha256,
+      "unencrypted_ecdsa.pub.randomart",
+    );
+    check_randomart(
+      "unencrypted_ecdsa384.pub",
+      FingerprintHash::Sha256,
+      "unencrypted_ecdsa384.pub.randomart",
+    );
+    check_randomart(
+      "unencrypted_ed25519.pub",
+      FingerprintHash::Sha256,
+      "unencrypted_ed25519.pub.randomart",
+    );
+    check_randomart(
+      "unencrypted_ed25519.pub",
+      FingerprintHash::Md5,
+      "unencrypted_ed25519.pub.randomart-md5",
+    );
+    check_randomart("sk_ed25519.pub", FingerprintHash::Sha256, "sk_ed25519.pub.randomart");
+    #[cfg(feature = "legacy-dsa")]
+    check_randomart(
+      "unencrypted_dsa.pub",
+      FingerprintHash::Sha256,
+      "unencrypted_dsa.pub.randomart",
+    );
+  }
+}
@@ -0,0 +1,210 @@
+use crate::error::{Error, Result};
+use crate::public::PublicKey;
+use std::fmt;
+
+const BEGIN: &str = "---- BEGIN SSH2 PUBLIC KEY ----";
+const END: &str = "---- END SSH2 PUBLIC KEY ----";
+// RFC 4716 limits lines to 72 bytes, ssh-keygen wraps the key data at 70 characters
+const MAX_LINE_LENGTH: usize = 72;
+const BASE64_LINE_LENGTH: usize = 70;
+
+/// A public key in the RFC 4716 "SSH2 PUBLIC KEY" format.
+#[derive(Debug)]
+pub struct Ssh2PublicKey {
+  pub key: PublicKey,
+  /// Header tags and values in file order, with surrounding quotes removed from the values.
+  pub headers: Vec<(String, String)>,
+}
+
+impl Ssh2PublicKey {
+  pub fn parse(text: &str) -> Result<Ssh2PublicKey> {
+    let mut lines = text
+      .lines()
+      .map(str::trim_end)
+      .skip_while(|line| line.trim().is_empty());
+
+    if lines.next() != Some(BEGIN) {
+      return Err(Error::CouldNotReadKey);
+    }
+
+    let mut headers = Vec::new();
+    let mut encoded = String::new();
+    let mut in_headers = true;
+    let mut continued: Option<String> = None;
+
+    loop {
+      let line = lines.next().ok_or(Error::CouldNotReadKey)?;
+
+      if let Some(mut header) = continued.take() {
+        header.push_str(line);
+        match header.strip_suffix('\\') {
+          Some(header) => continued = Some(header.to_string()),
+          None => headers.push(parse_header(&header)?),
+        }
+      } else if line == END {
+        break;
+      } else if in_headers && line.contains(':') {
+        match line.strip_suffix('\\') {
+          Some(header) => continued = Some(header.to_string()),
+          None => headers.push(parse_header(line)?),
+        }
+      } else {
+        in_headers = false;
+        encoded.push_str(line.trim());
+      }
+    }
+
+    let raw_key = base64::decode_config(&encoded, base64::STANDARD)?;
+    let key = PublicKey::parse_raw(&raw_key)?;
+
+    Ok(Ssh2PublicKey { key, headers })
+  }
+
+  pub fn header(&self, tag: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(name, _)| name.eq_ignore_ascii_case(tag))
+      .map(|(_, value)| value.as_str())
+  }
+
+  pub fn comment(&self) -> Option<&str> {
+    self.header("Comment")
+  }
+
+  pub fn subject(&self) -> Option<&str> {
+    self.header("Subject")
+  }
+
+  pub fn to_rfc4716(&self) -> String {
+    let mut result = String::new();
+
+    result.push_str(BEGIN);
+    result.push('\n');
+    for (tag, value) in &self.headers {
+      // A trailing backslash would read as continuation, quoting keeps it part of the value
+      let quote = value.contains(' ') || value.contains('"') || value.ends_with('\\');
+      let header = if quote || tag.eq_ignore_ascii_case("Comment") {
+        format!("{}: \"{}\"", tag, value)
+      } else {
+        format!("{}: {}", tag, value)
+      };
+      let mut rest = header.as_str();
+      while rest.len() > MAX_LINE_LENGTH {
+        let mut end = MAX_LINE_LENGTH - 1;
+        while !rest.is_char_boundary(end) {
+          end -= 1;
+        }
+        result.push_str(&rest[..end]);
+        result.push_str("\\\n");
+        rest = &rest[end..];
+      }
+      result.push_str(rest);
+      result.push('\n');
+    }
+    let encoded = base64::encode_config(self.key.to_ssh_key(), base64::STANDARD);
+    for chunk in encoded.as_bytes().chunks(BASE64_LINE_LENGTH) {
+      // base64 output is plain ascii
+      result.push_str(std::str::from_utf8(chunk).unwrap());
+      result.push('\n');
+    }
+    result.push_str(END);
+    result.push('\n');
+
+    result
+  }
+}
+
+impl fmt::Display for Ssh2PublicKey {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_rfc4716())
+  }
+}
+
+impl PublicKey {
+  pub fn parse_rfc4716(text: &str) -> Result<PublicKey> {
+    Ok(Ssh2PublicKey::parse(text)?.key)
+  }
+}
+
+fn parse_header(line: &str) -> Result<(String, String)> {
+  let idx = line.find(':').ok_or(Error::CouldNotReadKey)?;
+  let tag = line[..idx].trim();
+  let value = line[idx + 1..].trim();
+
+  if tag.is_empty() || tag.len() > 64 {
+    return Err(Error::CouldNotReadKey);
+  }
+  let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+    &value[1..value.len() - 1]
+  } else {
+    value
+  };
+
+  Ok((tag.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::fs;
+
+  #[test]
+  fn parse_ssh_keygen_export() {
+    let exported = fs::read_to_string("fixtures/unencrypted_rsa.rfc4716").unwrap();
+    let ssh2 = Ssh2PublicKey::parse(&exported).unwrap();
+    let reference = PublicKey::parse_pub(&fs::read_to_string("fixtures/unencrypted_rsa.pub").unwrap()).unwrap();
+
+    assert_eq!(ssh2.comment(), Some("2048-bit RSA, converted by root@vm from OpenSSH"));
+    assert_eq!(ssh2.key.to_ssh_key(), reference.to_ssh_key());
+    assert_eq!(ssh2.to_rfc4716(), exported);
+  }
+
+  #[test]
+  fn continued_headers() {
+    let key = PublicKey::parse_pub(&fs::read_to_string("fixtures/unencrypted_ed25519.pub").unwrap()).unwrap();
+    let ssh2 = Ssh2PublicKey {
+      key,
+      headers: vec![
+        ("Subject".to_string(), "operator".to_string()),
+        (
+          "Comment".to_string(),
+          "a rather long comment that does not fit into a single line of the key file at all".to_string(),
+        ),
+        ("x-vendor".to_string(), "value with \\ backslash".to_string()),
+        ("x-name".to_string(), "Jürgen Müller ".repeat(8).trim_end().to_string()),
+      ],
+    };
+    let written = ssh2.to_rfc4716();
+
+    assert!(written.lines().all(|line| line.len() <= MAX_LINE_LENGTH));
+
+    let parsed = Ssh2PublicKey::parse(&written).unwrap();
+
+    assert_eq!(parsed.headers, ssh2.headers);
+    assert_eq!(parsed.subject(), Some("operator"));
+    assert_eq!(parsed.key.to_ssh_key(), ssh2.key.to_ssh_key());
+  }
+
+  #[test]
+  fn trailing_backslash() {
+    let key = PublicKey::parse_pub(&fs::read_to_string("fixtures/unencrypted_ed25519.pub").unwrap()).unwrap();
+    let ssh2 = Ssh2PublicKey {
+      key,
+      headers: vec![
+        ("x-path".to_string(), "C:\\keys\\".to_string()),
+        ("Subject".to_string(), "operator".to_string()),
+      ],
+    };
+    let written = ssh2.to_rfc4716();
+
+    assert!(written.contains("x-path: \"C:\\keys\\\"\n"));
+    assert_eq!(Ssh2PublicKey::parse(&written).unwrap().headers, ssh2.headers);
+  }
+
+  #[test]
+  fn reject_garbage() {
+    assert!(PublicKey::parse_rfc4716("ssh-ed25519 AAAA").is_err());
+    assert!(PublicKey::parse_rfc4716(&format!("{}\nAAAA\n", BEGIN)).is_err());
+  }
+}
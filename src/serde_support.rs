@@ -0,0 +1,93 @@
+use crate::public::PublicKey;
+use crate::signature::Signature;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+/// Serialized as an OpenSSH public key line without comment, e.g. `ssh-ed25519 AAAA...`.
+impl Serialize for PublicKey {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for PublicKey {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+  }
+}
+
+/// Serialized as the base64 encoded wire blob.
+impl Serialize for Signature {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for Signature {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::agent::Identity;
+  use crate::public::PublicKey;
+  use crate::signature::{Signature, SignatureHash};
+  use std::collections::HashSet;
+  use std::fs;
+
+  fn fixture_line(name: &str) -> String {
+    let line = fs::read_to_string(format!("fixtures/{}", name)).unwrap();
+    let parts: Vec<&str> = line.split(' ').collect();
+    format!("{} {}", parts[0], parts[1])
+  }
+
+  #[test]
+  fn public_key_as_openssh_line() {
+    let line = fixture_line("unencrypted_ecdsa.pub");
+    let key = PublicKey::parse_pub(&line).unwrap();
+    let json = serde_json::to_string(&key).unwrap();
+
+    assert_eq!(json, format!("\"{}\"", line));
+    assert_eq!(serde_json::from_str::<PublicKey>(&json).unwrap(), key);
+    assert!(serde_json::from_str::<PublicKey>("\"ssh-ed25519 AAAA\"").is_err());
+  }
+
+  #[test]
+  fn unknown_key_roundtrip() {
+    let mut writer = crate::encoding::Writer::new();
+    writer.write_string(b"unknown@example.com");
+    writer.write_string(&[1, 2, 3]);
+    let identity = Identity {
+      key: PublicKey::parse_raw_or_unknown(&writer.buffer).unwrap(),
+      comment: "vendor key".to_string(),
+    };
+    let json = serde_json::to_string(&identity).unwrap();
+
+    assert_eq!(serde_json::from_str::<Identity>(&json).unwrap(), identity);
+  }
+
+  #[test]
+  fn identity_and_signature() {
+    let identity = Identity {
+      key: PublicKey::parse_pub(&fixture_line("unencrypted_ed25519.pub")).unwrap(),
+      comment: "deploy".to_string(),
+    };
+    let signature = Signature {
+      hash: SignatureHash::Ed25519,
+      signature: vec![1; 64],
+      security_key: None,
+    };
+    let json = serde_json::to_string(&(&identity, &signature)).unwrap();
+    let (parsed_identity, parsed_signature): (Identity, Signature) = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(parsed_identity, identity);
+    assert_eq!(parsed_signature, signature);
+
+    let mut identities = HashSet::new();
+    identities.insert(identity.clone());
+    identities.insert(parsed_identity);
+    assert_eq!(identities.len(), 1);
+  }
+}
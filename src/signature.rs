@@ -1,19 +1,33 @@
 use crate::encoding::{Reader, Writer};
 use crate::error::{Error, Result};
 use crate::mini_der;
+use crate::mini_ec;
 use crate::public::{
-  PublicKey, SSH_ECDSA_P256, SSH_ECDSA_P384, SSH_ED25519, SSH_RSA, SSH_RSA_SHA2_256, SSH_RSA_SHA2_512,
+  mpint_bits, PublicKey, SSH_ECDSA_P256, SSH_ECDSA_P384, SSH_ECDSA_P521, SSH_ED25519, SSH_RSA, SSH_RSA_SHA2_256,
+  SSH_RSA_SHA2_512, SSH_SK_ECDSA_P256, SSH_SK_ED25519,
 };
-use ring::signature;
+#[cfg(feature = "legacy-dsa")]
+use crate::{mini_dsa, public::SSH_DSS};
+use ring::{digest, signature};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum SignatureHash {
   RsaSha1,
   RsaSha256,
   RsaSha512,
   EcdsaP256,
   EcdsaP384,
+  EcdsaP521,
   Ed25519,
+  SkEcdsaP256,
+  SkEd25519,
+  #[cfg(feature = "legacy-dsa")]
+  Dss,
 }
 
 impl SignatureHash {
@@ -24,16 +38,140 @@ impl SignatureHash {
       SSH_RSA_SHA2_512 => Ok(SignatureHash::RsaSha512),
       SSH_ECDSA_P256 => Ok(SignatureHash::EcdsaP256),
       SSH_ECDSA_P384 => Ok(SignatureHash::EcdsaP384),
+      SSH_ECDSA_P521 => Ok(SignatureHash::EcdsaP521),
       SSH_ED25519 => Ok(SignatureHash::Ed25519),
+      SSH_SK_ECDSA_P256 => Ok(SignatureHash::SkEcdsaP256),
+      SSH_SK_ED25519 => Ok(SignatureHash::SkEd25519),
+      #[cfg(feature = "legacy-dsa")]
+      SSH_DSS => Ok(SignatureHash::Dss),
       _ => Err(Error::InvalidSignature),
     }
   }
+
+  /// Wire name of the signature algorithm.
+  pub fn name(&self) -> &'static str {
+    match self {
+      SignatureHash::RsaSha1 => "ssh-rsa",
+      SignatureHash::RsaSha256 => "rsa-sha2-256",
+      SignatureHash::RsaSha512 => "rsa-sha2-512",
+      SignatureHash::EcdsaP256 => "ecdsa-sha2-nistp256",
+      SignatureHash::EcdsaP384 => "ecdsa-sha2-nistp384",
+      SignatureHash::EcdsaP521 => "ecdsa-sha2-nistp521",
+      SignatureHash::Ed25519 => "ssh-ed25519",
+      SignatureHash::SkEcdsaP256 => "sk-ecdsa-sha2-nistp256@openssh.com",
+      SignatureHash::SkEd25519 => "sk-ssh-ed25519@openssh.com",
+      #[cfg(feature = "legacy-dsa")]
+      SignatureHash::Dss => "ssh-dss",
+    }
+  }
+
+  /// Whether the algorithm depends on SHA-1.
+  pub fn is_sha1(&self) -> bool {
+    match self {
+      SignatureHash::RsaSha1 => true,
+      #[cfg(feature = "legacy-dsa")]
+      SignatureHash::Dss => true,
+      _ => false,
+    }
+  }
+}
+
+impl fmt::Display for SignatureHash {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.name())
+  }
+}
+
+impl FromStr for SignatureHash {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<SignatureHash> {
+    SignatureHash::from_name(s.as_bytes())
+  }
+}
+
+/// Authenticator data appended to signatures made by a FIDO security key.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct SecurityKeyInfo {
+  pub flags: u8,
+  pub counter: u32,
+}
+
+impl SecurityKeyInfo {
+  pub const USER_PRESENT: u8 = 0x01;
+  pub const USER_VERIFIED: u8 = 0x04;
+
+  pub fn user_present(&self) -> bool {
+    self.flags & Self::USER_PRESENT != 0
+  }
+
+  pub fn user_verified(&self) -> bool {
+    self.flags & Self::USER_VERIFIED != 0
+  }
+}
+
+/// Crypto policy for `Signature::verify_with`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyOptions {
+  /// Signature algorithms to accept, `None` accepts all of them (subject to `allow_sha1`).
+  pub allowed_hashes: Option<HashSet<SignatureHash>>,
+  /// Minimum size of an RSA modulus in bits. Keys below 1024 bits can not be verified at all.
+  pub min_rsa_bits: u32,
+  /// Accept SHA-1 based signatures (`ssh-rsa` and `ssh-dss`). Only meant for a migration window with old
+  /// agents and servers.
+  pub allow_sha1: bool,
+  /// Reject security key signatures without the user presence flag, as sshd does unless `no-touch-required`
+  /// is set.
+  pub require_user_presence: bool,
+}
+
+impl VerifyOptions {
+  /// Only accept the given signature algorithms.
+  pub fn allowing(hashes: &[SignatureHash]) -> VerifyOptions {
+    VerifyOptions {
+      allowed_hashes: Some(hashes.iter().copied().collect()),
+      allow_sha1: hashes.iter().any(SignatureHash::is_sha1),
+      ..VerifyOptions::default()
+    }
+  }
+
+  /// Check if a signature with `hash` made by `key` is acceptable, without verifying it.
+  pub fn check(&self, hash: SignatureHash, key: &PublicKey) -> Result<()> {
+    let allowed = match &self.allowed_hashes {
+      Some(allowed) => allowed.contains(&hash),
+      None => true,
+    };
+    if !allowed || (hash.is_sha1() && !self.allow_sha1) {
+      return Err(Error::AlgorithmNotAllowed(hash.name().to_string()));
+    }
+    if let PublicKey::Rsa { n, .. } = key {
+      let bits = mpint_bits(n);
+      if bits < self.min_rsa_bits {
+        return Err(Error::KeyTooSmall(bits, self.min_rsa_bits));
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Default for VerifyOptions {
+  fn default() -> Self {
+    VerifyOptions {
+      allowed_hashes: None,
+      min_rsa_bits: 2048,
+      allow_sha1: false,
+      require_user_presence: true,
+    }
+  }
 }
 
-#[derive(Debug)]
+/// Equality, hashing and ordering are all based on the wire blob, see `to_ssh_sig`.
+#[derive(Debug, Clone)]
 pub struct Signature {
   pub hash: SignatureHash,
   pub signature: Vec<u8>,
+  /// Only set for security key signatures.
+  pub security_key: Option<SecurityKeyInfo>,
 }
 
 impl Signature {
@@ -43,30 +181,40 @@ impl Signature {
     let algo = reader.read_string()?;
     let hash = SignatureHash::from_name(algo)?;
     let signature = Vec::from(reader.read_string()?);
+    let security_key = match hash {
+      SignatureHash::SkEcdsaP256 | SignatureHash::SkEd25519 => Some(SecurityKeyInfo {
+        flags: reader.read_u8()?,
+        counter: reader.read_u32()?,
+      }),
+      _ => None,
+    };
 
-    Ok((Signature { hash, signature }, reader.remaining()))
+    Ok((
+      Signature {
+        hash,
+        signature,
+        security_key,
+      },
+      reader.remaining(),
+    ))
   }
 
   pub fn to_ssh_sig(&self) -> Vec<u8> {
     let mut writer = Writer::new();
 
-    match self.hash {
-      SignatureHash::RsaSha1 => writer.write_string(SSH_RSA),
-      SignatureHash::RsaSha256 => writer.write_string(SSH_RSA_SHA2_256),
-      SignatureHash::RsaSha512 => writer.write_string(SSH_RSA_SHA2_512),
-      SignatureHash::EcdsaP256 => writer.write_string(SSH_ECDSA_P256),
-      SignatureHash::EcdsaP384 => writer.write_string(SSH_ECDSA_P384),
-      SignatureHash::Ed25519 => writer.write_string(SSH_ED25519),
-    }
-
+    writer.write_string(self.hash.name().as_bytes());
     writer.write_string(&self.signature);
+    if let Some(info) = &self.security_key {
+      writer.write_u8(info.flags);
+      writer.write_u32(info.counter);
+    }
 
     writer.buffer
   }
 
   pub fn to_ring_sig(&self) -> Result<Vec<u8>> {
     match self.hash {
-      SignatureHash::EcdsaP256 | SignatureHash::EcdsaP384 => {
+      SignatureHash::EcdsaP256 | SignatureHash::EcdsaP384 | SignatureHash::SkEcdsaP256 => {
         let mut reader = Reader::new(&self.signature);
         let r = reader.read_string()?;
         let s = reader.read_string()?;
@@ -78,26 +226,308 @@ impl Signature {
   }
 
   pub fn verify(&self, key: &PublicKey, data: &[u8]) -> Result<()> {
+    self.verify_with(key, data, &VerifyOptions::default())
+  }
+
+  /// Signatures made with a certificate are verified against the certified key.
+  pub fn verify_with(&self, key: &PublicKey, data: &[u8], options: &VerifyOptions) -> Result<()> {
+    if let PublicKey::Certificate(cert) = key {
+      return self.verify_with(&cert.key, data, options);
+    }
+    options.check(self.hash, key)?;
+    if let Some(info) = &self.security_key {
+      if options.require_user_presence && !info.user_present() {
+        return Err(Error::UserPresenceRequired);
+      }
+    }
+
+    if let (SignatureHash::EcdsaP521, PublicKey::EcdsaP521(point)) = (&self.hash, key) {
+      // ring does not support P-521
+      let mut reader = Reader::new(&self.signature);
+      let r = reader.read_string()?;
+      let s = reader.read_string()?;
+      let digest = digest::digest(&digest::SHA512, data);
+
+      return if mini_ec::Curve::p521().verify(point, digest.as_ref(), r, s) {
+        Ok(())
+      } else {
+        Err(Error::SignatureDoesNotMatch)
+      };
+    }
+
+    #[cfg(feature = "legacy-dsa")]
+    {
+      if let (SignatureHash::Dss, PublicKey::Dsa { p, q, g, y }) = (&self.hash, key) {
+        // ssh-dss signatures are r || s, 20 bytes each, over SHA-1
+        if self.signature.len() != 40 {
+          return Err(Error::InvalidSignature);
+        }
+        let (r, s) = self.signature.split_at(20);
+        let digest = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data);
+
+        return if mini_dsa::verify(p, q, g, y, digest.as_ref(), r, s) {
+          Ok(())
+        } else {
+          Err(Error::SignatureDoesNotMatch)
+        };
+      }
+    }
+
     let algorithm: &dyn signature::VerificationAlgorithm = match (&self.hash, key) {
-      (SignatureHash::RsaSha256, PublicKey::Rsa { .. }) => &signature::RSA_PKCS1_2048_8192_SHA256,
-      (SignatureHash::RsaSha512, PublicKey::Rsa { .. }) => &signature::RSA_PKCS1_2048_8192_SHA512,
+      // The minimum key size has already been checked against the options
+      (SignatureHash::RsaSha1, PublicKey::Rsa { .. }) => &signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY,
+      (SignatureHash::RsaSha256, PublicKey::Rsa { .. }) => &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+      (SignatureHash::RsaSha512, PublicKey::Rsa { .. }) => &signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY,
       (SignatureHash::EcdsaP256, PublicKey::EcdsaP256(_)) => &signature::ECDSA_P256_SHA256_ASN1,
       (SignatureHash::EcdsaP384, PublicKey::EcdsaP384(_)) => &signature::ECDSA_P384_SHA384_ASN1,
       (SignatureHash::Ed25519, PublicKey::Ed25519(_)) => &signature::ED25519,
+      (SignatureHash::SkEcdsaP256, PublicKey::SkEcdsaP256 { .. }) => &signature::ECDSA_P256_SHA256_ASN1,
+      (SignatureHash::SkEd25519, PublicKey::SkEd25519 { .. }) => &signature::ED25519,
       _ => return Err(Error::InvalidSignature),
     };
 
-    let peer_public_key =
-        signature::UnparsedPublicKey::new(algorithm, key.to_ring_key());
+    let peer_public_key = signature::UnparsedPublicKey::new(algorithm, key.to_ring_key());
 
     let ring_sig = self.to_ring_sig()?;
+    let message = match (key, &self.security_key) {
+      (PublicKey::SkEcdsaP256 { application, .. }, Some(info))
+      | (PublicKey::SkEd25519 { application, .. }, Some(info)) => security_key_message(application, info, data),
+      (PublicKey::SkEcdsaP256 { .. }, None) | (PublicKey::SkEd25519 { .. }, None) => {
+        return Err(Error::InvalidSignature)
+      }
+      _ => data.to_vec(),
+    };
 
-    if peer_public_key.verify(data,&ring_sig)
-    .is_ok()
-    {
+    if peer_public_key.verify(&message, &ring_sig).is_ok() {
       Ok(())
     } else {
       Err(Error::SignatureDoesNotMatch)
     }
   }
 }
+
+/// Base64 encoded wire blob.
+impl fmt::Display for Signature {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", base64::encode_config(self.to_ssh_sig(), base64::STANDARD))
+  }
+}
+
+impl FromStr for Signature {
+  type Err = Error;
+
+  fn from_str(s: &str) -> Result<Signature> {
+    let raw = base64::decode_config(s.trim(), base64::STANDARD)?;
+    let (signature, remaining) = Signature::parse_raw(&raw)?;
+
+    if !remaining.is_empty() {
+      return Err(Error::InvalidSignature);
+    }
+    Ok(signature)
+  }
+}
+
+impl PartialEq for Signature {
+  fn eq(&self, other: &Self) -> bool {
+    self.to_ssh_sig() == other.to_ssh_sig()
+  }
+}
+
+impl Eq for Signature {}
+
+impl Hash for Signature {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.to_ssh_sig().hash(state)
+  }
+}
+
+impl PartialOrd for Signature {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Signature {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.to_ssh_sig().cmp(&other.to_ssh_sig())
+  }
+}
+
+// The authenticator signs the hashed application and data together with its flags and counter
+fn security_key_message(application: &str, info: &SecurityKeyInfo, data: &[u8]) -> Vec<u8> {
+  let mut message = Vec::with_capacity(2 * digest::SHA256_OUTPUT_LEN + 5);
+
+  message.extend_from_slice(digest::digest(&digest::SHA256, application.as_bytes()).as_ref());
+  message.push(info.flags);
+  message.extend_from_slice(&info.counter.to_be_bytes());
+  message.extend_from_slice(digest::digest(&digest::SHA256, data).as_ref());
+
+  message
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ring::rand::SystemRandom;
+  use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair};
+
+  const APPLICATION: &str = "ssh:";
+  const INFO: SecurityKeyInfo = SecurityKeyInfo {
+    flags: SecurityKeyInfo::USER_PRESENT,
+    counter: 42,
+  };
+
+  fn mpint(value: &[u8]) -> Vec<u8> {
+    let value = &value[value.iter().position(|b| *b != 0).unwrap_or(value.len())..];
+    match value.first() {
+      Some(b) if b & 0x80 != 0 => [&[0], value].concat(),
+      _ => value.to_vec(),
+    }
+  }
+
+  fn sk_signature(hash: SignatureHash, signature: Vec<u8>) -> Signature {
+    let blob = Signature {
+      hash,
+      signature,
+      security_key: Some(INFO),
+    }
+    .to_ssh_sig();
+    let (signature, remaining) = Signature::parse_raw(&blob).unwrap();
+
+    assert!(remaining.is_empty());
+    signature
+  }
+
+  #[test]
+  fn text_forms() {
+    for hash in [
+      SignatureHash::RsaSha1,
+      SignatureHash::EcdsaP521,
+      SignatureHash::SkEd25519,
+    ]
+    .iter()
+    {
+      assert_eq!(hash.to_string().parse::<SignatureHash>().unwrap(), *hash);
+    }
+    assert_eq!(
+      "rsa-sha2-256".parse::<SignatureHash>().unwrap(),
+      SignatureHash::RsaSha256
+    );
+    assert!("rsa-sha2-384".parse::<SignatureHash>().is_err());
+
+    let signature = Signature {
+      hash: SignatureHash::Ed25519,
+      signature: vec![7; 64],
+      security_key: None,
+    };
+    let text = signature.to_string();
+
+    assert!(text.starts_with("AAAAC3NzaC1lZDI1NTE5"));
+    assert_eq!(text.parse::<Signature>().unwrap(), signature);
+
+    let trailing = base64::encode([signature.to_ssh_sig(), vec![0]].concat());
+    assert!(trailing.parse::<Signature>().is_err());
+  }
+
+  #[test]
+  fn verify_options() {
+    let line = std::fs::read_to_string("fixtures/unencrypted_rsa.pub").unwrap();
+    let rsa = PublicKey::parse_pub(&line).unwrap();
+    let sha2_only = VerifyOptions::allowing(&[SignatureHash::RsaSha256, SignatureHash::RsaSha512]);
+
+    assert!(sha2_only.check(SignatureHash::RsaSha512, &rsa).is_ok());
+    match sha2_only.check(SignatureHash::RsaSha1, &rsa) {
+      Err(Error::AlgorithmNotAllowed(name)) => assert_eq!(name, "ssh-rsa"),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    match VerifyOptions::default().check(SignatureHash::RsaSha1, &rsa) {
+      Err(Error::AlgorithmNotAllowed(_)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    assert!(VerifyOptions::allowing(&[SignatureHash::RsaSha1])
+      .check(SignatureHash::RsaSha1, &rsa)
+      .is_ok());
+
+    let large_keys = VerifyOptions {
+      min_rsa_bits: 3072,
+      ..VerifyOptions::default()
+    };
+    match large_keys.check(SignatureHash::RsaSha512, &rsa) {
+      Err(Error::KeyTooSmall(2048, 3072)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+  }
+
+  #[test]
+  fn sk_ed25519_signature() {
+    let rng = SystemRandom::new();
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+    let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let key = PublicKey::SkEd25519 {
+      key: pair.public_key().as_ref().to_vec(),
+      application: APPLICATION.to_string(),
+    };
+    let raw = pair.sign(&security_key_message(APPLICATION, &INFO, b"data"));
+    let signature = sk_signature(SignatureHash::SkEd25519, raw.as_ref().to_vec());
+
+    assert_eq!(signature.security_key, Some(INFO));
+    assert!(signature.security_key.unwrap().user_present());
+    assert!(!signature.security_key.unwrap().user_verified());
+    assert!(signature.verify(&key, b"data").is_ok());
+    assert!(signature.verify(&key, b"other").is_err());
+    match signature.verify_with(&key, b"data", &VerifyOptions::allowing(&[SignatureHash::Ed25519])) {
+      Err(Error::AlgorithmNotAllowed(_)) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+
+    // Without the user presence flag the signature is only accepted when explicitly allowed
+    let untouched = SecurityKeyInfo { flags: 0, ..INFO };
+    let raw = pair.sign(&security_key_message(APPLICATION, &untouched, b"data"));
+    let signature = Signature {
+      hash: SignatureHash::SkEd25519,
+      signature: raw.as_ref().to_vec(),
+      security_key: Some(untouched),
+    };
+    match signature.verify(&key, b"data") {
+      Err(Error::UserPresenceRequired) => (),
+      other => panic!("Unexpected result: {:?}", other),
+    }
+    let no_touch = VerifyOptions {
+      require_user_presence: false,
+      ..VerifyOptions::default()
+    };
+    assert!(signature.verify_with(&key, b"data", &no_touch).is_ok());
+
+    let other_application = PublicKey::SkEd25519 {
+      key: pair.public_key().as_ref().to_vec(),
+      application: "ssh:other".to_string(),
+    };
+    assert!(signature.verify(&other_application, b"data").is_err());
+  }
+
+  #[test]
+  fn sk_ecdsa_signature() {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+    let pair = EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref()).unwrap();
+    let key = PublicKey::SkEcdsaP256 {
+      key: pair.public_key().as_ref().to_vec(),
+      application: APPLICATION.to_string(),
+    };
+    let fixed = pair
+      .sign(&rng, &security_key_message(APPLICATION, &INFO, b"data"))
+      .unwrap();
+    let (r, s) = fixed.as_ref().split_at(32);
+    let mut writer = Writer::new();
+    writer.write_string(&mpint(r));
+    writer.write_string(&mpint(s));
+    let signature = sk_signature(SignatureHash::SkEcdsaP256, writer.buffer);
+
+    assert!(signature.verify(&key, b"data").is_ok());
+    assert!(signature.verify(&key, b"other").is_err());
+
+    let mut tampered = signature;
+    tampered.security_key = Some(SecurityKeyInfo { counter: 43, ..INFO });
+    assert!(tampered.verify(&key, b"data").is_err());
+  }
+}